@@ -1,8 +1,28 @@
+use serde::{Deserialize, Serialize};
+
 ///
 /// Enum representing all types of operations on unary expressions
 /// This is also used as a field to UnaryExpr
 /// 
-#[derive(Debug, Clone)]
+/// Note that the trigonometric operations work in degrees, INT truncates
+/// towards zero and RANDOM n picks a whole number from 0 to n - 1
+/// 
+/// Note that FIRST, LAST, BUTFIRST, BUTLAST, COUNT and EMPTYP work on both
+/// lists and words, where the items of a word are its characters
+/// 
+/// Note that NUMBERP and WORDP test the kind of their child, UPPERCASE and
+/// LOWERCASE only change letters, CHAR gives the character for a character
+/// code and ASCII gives the character code for the first character of a word
+/// 
+/// Note that PRINT writes its child followed by a newline, leaving off the
+/// outer brackets of a list, SHOW is the same but keeps the brackets and
+/// TYPE is the same as PRINT without the newline
+/// 
+/// Note that SETPOS takes a [x y] list, CIRCLE draws a circle of the given
+/// radius centred on the turtle without moving it, and TOWARDS and DISTANCE
+/// give the heading to face, and distance to, a [x y] position
+/// 
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum UnaryOp {
     Forward,
     Back,
@@ -13,13 +33,145 @@ pub enum UnaryOp {
     Setheading,
     Setx,
     Sety,
+    Output,
+    Not,
+    Sqrt,
+    Abs,
+    Int,
+    Round,
+    Sin,
+    Cos,
+    Tan,
+    Arctan,
+    Random,
+    First,
+    Last,
+    Butfirst,
+    Butlast,
+    Count,
+    Emptyp,
+    Uppercase,
+    Lowercase,
+    Numberp,
+    Wordp,
+    Char,
+    Ascii,
+    Print,
+    Show,
+    Type,
+    Local,
+    Setpos,
+    Circle,
+    Towards,
+    Distance,
+    Setpensize,
+    Setbackground,
+}
+
+impl UnaryOp {
+    ///
+    /// Returns whether the operation outputs a value, where the
+    /// other operations are commands
+    ///
+    pub fn reports(&self) -> bool {
+        matches!(
+            self,
+            UnaryOp::Not
+                | UnaryOp::Sqrt
+                | UnaryOp::Abs
+                | UnaryOp::Int
+                | UnaryOp::Round
+                | UnaryOp::Sin
+                | UnaryOp::Cos
+                | UnaryOp::Tan
+                | UnaryOp::Arctan
+                | UnaryOp::Random
+                | UnaryOp::First
+                | UnaryOp::Last
+                | UnaryOp::Butfirst
+                | UnaryOp::Butlast
+                | UnaryOp::Count
+                | UnaryOp::Emptyp
+                | UnaryOp::Uppercase
+                | UnaryOp::Lowercase
+                | UnaryOp::Numberp
+                | UnaryOp::Wordp
+                | UnaryOp::Char
+                | UnaryOp::Ascii
+                | UnaryOp::Towards
+                | UnaryOp::Distance
+        )
+    }
+
+    ///
+    /// Returns the keyword used for this operation in logo code
+    ///
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            UnaryOp::Forward => "FORWARD",
+            UnaryOp::Back => "BACK",
+            UnaryOp::Left => "LEFT",
+            UnaryOp::Right => "RIGHT",
+            UnaryOp::Setpencolor => "SETPENCOLOR",
+            UnaryOp::Turn => "TURN",
+            UnaryOp::Setheading => "SETHEADING",
+            UnaryOp::Setx => "SETX",
+            UnaryOp::Sety => "SETY",
+            UnaryOp::Output => "OUTPUT",
+            UnaryOp::Not => "NOT",
+            UnaryOp::Sqrt => "SQRT",
+            UnaryOp::Abs => "ABS",
+            UnaryOp::Int => "INT",
+            UnaryOp::Round => "ROUND",
+            UnaryOp::Sin => "SIN",
+            UnaryOp::Cos => "COS",
+            UnaryOp::Tan => "TAN",
+            UnaryOp::Arctan => "ARCTAN",
+            UnaryOp::Random => "RANDOM",
+            UnaryOp::First => "FIRST",
+            UnaryOp::Last => "LAST",
+            UnaryOp::Butfirst => "BUTFIRST",
+            UnaryOp::Butlast => "BUTLAST",
+            UnaryOp::Count => "COUNT",
+            UnaryOp::Emptyp => "EMPTYP",
+            UnaryOp::Uppercase => "UPPERCASE",
+            UnaryOp::Lowercase => "LOWERCASE",
+            UnaryOp::Numberp => "NUMBERP",
+            UnaryOp::Wordp => "WORDP",
+            UnaryOp::Char => "CHAR",
+            UnaryOp::Ascii => "ASCII",
+            UnaryOp::Print => "PRINT",
+            UnaryOp::Show => "SHOW",
+            UnaryOp::Type => "TYPE",
+            UnaryOp::Local => "LOCAL",
+            UnaryOp::Setpos => "SETPOS",
+            UnaryOp::Circle => "CIRCLE",
+            UnaryOp::Towards => "TOWARDS",
+            UnaryOp::Distance => "DISTANCE",
+            UnaryOp::Setpensize => "SETPENSIZE",
+            UnaryOp::Setbackground => "SETBACKGROUND",
+        }
+    }
 }
 
 ///
 /// Enum representing all types of operations on binary expressions
 /// This is also used as a field to BinaryExpr
 ///
-#[derive(Debug, Clone)]
+/// Note that the result of MODULO takes the sign of the divisor (rhs)
+/// whereas the result of REMAINDER takes the sign of the dividend (lhs)
+///
+/// Note that ITEM counts from 1, FPUT and LPUT add the lhs to the front or
+/// back of the rhs list, LIST makes a two item list and SENTENCE joins both
+/// sides into one list, taking the items out of any list argument
+///
+/// Note that WORD joins two words into one and MEMBERP tests whether lhs is
+/// an item of the rhs list, or a character of the rhs word
+///
+/// Note that ARC draws an arc of lhs degrees clockwise from the heading
+/// with a radius of rhs, centred on the turtle without moving it
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum BinaryOp {
     Make,
     Addassign,
@@ -29,37 +181,181 @@ pub enum BinaryOp {
     Sub,
     Mul,
     Div,
+    Power,
+    Modulo,
+    Remainder,
     Eq,
     Ne,
     Gt,
     Lt,
+    Ge,
+    Le,
     And,
     Or,
+    Xor,
+    Item,
+    Fput,
+    Lput,
+    List,
+    Sentence,
+    Word,
+    Memberp,
+    Localmake,
+    Setxy,
+    Arc,
     Func(String),
 }
 
+impl BinaryOp {
+    ///
+    /// Returns whether the operation outputs a value, where the
+    /// other operations are commands
+    ///
+    pub fn reports(&self) -> bool {
+        !matches!(
+            self,
+            BinaryOp::Make
+                | BinaryOp::Addassign
+                | BinaryOp::Localmake
+                | BinaryOp::If
+                | BinaryOp::While
+                | BinaryOp::Setxy
+                | BinaryOp::Arc
+                | BinaryOp::Func(_)
+        )
+    }
+
+    ///
+    /// Returns the keyword used for this operation in logo code
+    ///
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            BinaryOp::Make => "MAKE",
+            BinaryOp::Addassign => "ADDASSIGN",
+            BinaryOp::If => "IF",
+            BinaryOp::While => "WHILE",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Power => "POWER",
+            BinaryOp::Modulo => "MODULO",
+            BinaryOp::Remainder => "REMAINDER",
+            BinaryOp::Eq => "EQ",
+            BinaryOp::Ne => "NE",
+            BinaryOp::Gt => "GT",
+            BinaryOp::Lt => "LT",
+            BinaryOp::Ge => "GE",
+            BinaryOp::Le => "LE",
+            BinaryOp::And => "AND",
+            BinaryOp::Or => "OR",
+            BinaryOp::Xor => "XOR",
+            BinaryOp::Item => "ITEM",
+            BinaryOp::Fput => "FPUT",
+            BinaryOp::Lput => "LPUT",
+            BinaryOp::List => "LIST",
+            BinaryOp::Sentence => "SENTENCE",
+            BinaryOp::Word => "WORD",
+            BinaryOp::Memberp => "MEMBERP",
+            BinaryOp::Localmake => "LOCALMAKE",
+            BinaryOp::Setxy => "SETXY",
+            BinaryOp::Arc => "ARC",
+            BinaryOp::Func(_) => "TO",
+        }
+    }
+}
+
+///
+/// Enum representing all types of operations on ternary expressions
+/// This is also used as a field to TernaryExpr
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TernaryOp {
+    IfElse,
+}
+
+impl TernaryOp {
+    ///
+    /// Returns the keyword used for this operation in logo code
+    ///
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            TernaryOp::IfElse => "IFELSE",
+        }
+    }
+}
+
 ///
 /// Enum representing all types of expressions in the AST as 'Node'
 /// Some expressions represent terminal nodes such as literals and variables
 /// whereas other expressions represent internal nodes such as operators
 /// 
+/// Note that every command parsed as its own line of code (including those
+/// in a codeblock or function body) is wrapped in a 'Statement' which
+/// stores the Span of the token it starts at
+/// 
+/// Note that a 'Variable' stores the Scope it resolves to, which stays
+/// Unresolved until the resolver pass has been run over the AST
+/// 
+/// Note that 'Home' moves the turtle back to the origin facing up,
+/// 'Clean' erases the drawing and 'Clearscreen' does both. 'Pos' gives
+/// the position of the turtle as a [x y] list
+/// 
+/// Note that SETPENCOLOR and SETBACKGROUND take a palette index, a [r g b]
+/// list, a hex word such as "#ff8800 or a palette name such as "red, as
+/// described by Colour. 'Color' gives the pen colour as its palette index,
+/// or as a [r g b] list when it is not in the palette
+/// 
+/// Note that 'Penpaint' draws with the pen colour, 'Penerase' draws with
+/// the background colour and 'Penreverse' inverts whatever is drawn over.
+/// Each of them also puts the pen down. 'Pensize' gives the pen width and
+/// 'Pendownp' gives whether the pen is down
+/// 
+/// Note that a 'List' is a data list such as [1 2 3] where each item is a
+/// Literal or a nested List. A '[' opens a data list wherever an expression
+/// is expected, and opens a codeblock everywhere else
+/// 
 /// Note that 'if' and 'while' statements are broken into condition-body
 /// where the lhs stores the condition and rhs stores the body
 /// 
+/// Note that 'ifelse' statements are broken into condition-then-else
+/// where first stores the condition, second stores the body run when the
+/// condition is true and third stores the body run otherwise
+/// 
+/// Note that 'for' loops bind var to each value from start to end
+/// (inclusive) in increments of step. When step is omitted it is 1, or -1
+/// if end is less than start. A negative step counts down
+/// 
 /// Note that 'defined function calls' are broken into argument-body
 /// where the lhs stores the arguments and rhs references the body of the function
+/// 
+/// Note that a 'Caller' may also appear inside an expression, in which case
+/// its value is whatever the called function passes to OUTPUT. OUTPUT and
+/// STOP both return from the function they appear in
 ///
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[allow(dead_code)]
 pub enum Node {
     Literal(String),
-    Variable(String),
+    Variable(String, Scope),
+    List(Vec<Node>),
     Xcor,
     Ycor,
     Heading,
+    Pos,
+    Pi,
     Color,
+    Pensize,
+    Pendownp,
     Penup,
     Pendown,
+    Penpaint,
+    Penerase,
+    Penreverse,
+    Stop,
+    Home,
+    Clearscreen,
+    Clean,
     UnaryExpr {
         op: UnaryOp,
         child: Box<Node>,
@@ -69,7 +365,24 @@ pub enum Node {
         lhs: Box<Node>,
         rhs: Box<Node>,
     },
+    TernaryExpr {
+        op: TernaryOp,
+        first: Box<Node>,
+        second: Box<Node>,
+        third: Box<Node>,
+    },
+    For {
+        var: String,
+        start: Box<Node>,
+        end: Box<Node>,
+        step: Option<Box<Node>>,
+        body: Box<Node>,
+    },
     Body(Vec<Node>),
+    Statement {
+        span: Span,
+        node: Box<Node>,
+    },
     Empty,
     Newline,
     Caller {
@@ -78,12 +391,288 @@ pub enum Node {
     },
 }
 
+impl Node {
+    ///
+    /// Returns the keyword used for this node in logo code, for the
+    /// nodes that are written as a single keyword such as PENUP or XCOR
+    ///
+    pub fn keyword(&self) -> Option<&'static str> {
+        match self {
+            Node::Xcor => Some("XCOR"),
+            Node::Ycor => Some("YCOR"),
+            Node::Heading => Some("HEADING"),
+            Node::Pos => Some("POS"),
+            Node::Pi => Some("PI"),
+            Node::Color => Some("COLOR"),
+            Node::Pensize => Some("PENSIZE"),
+            Node::Pendownp => Some("PENDOWNP"),
+            Node::Penup => Some("PENUP"),
+            Node::Pendown => Some("PENDOWN"),
+            Node::Penpaint => Some("PENPAINT"),
+            Node::Penerase => Some("PENERASE"),
+            Node::Penreverse => Some("PENREVERSE"),
+            Node::Stop => Some("STOP"),
+            Node::Home => Some("HOME"),
+            Node::Clearscreen => Some("CLEARSCREEN"),
+            Node::Clean => Some("CLEAN"),
+            _ => None,
+        }
+    }
+
+    ///
+    /// Returns a copy of the node with the span of every statement in it
+    /// cleared, so that it can be compared or hashed by what it does
+    /// regardless of where it was written
+    ///
+    pub fn without_spans(&self) -> Node {
+        let boxed = |node: &Node| Box::new(node.without_spans());
+        match self {
+            Node::List(nodes) => Node::List(nodes.iter().map(Node::without_spans).collect()),
+            Node::Body(nodes) => Node::Body(nodes.iter().map(Node::without_spans).collect()),
+            Node::UnaryExpr { op, child } => Node::UnaryExpr {
+                op: op.clone(),
+                child: boxed(child),
+            },
+            Node::BinaryExpr { op, lhs, rhs } => Node::BinaryExpr {
+                op: op.clone(),
+                lhs: boxed(lhs),
+                rhs: boxed(rhs),
+            },
+            Node::TernaryExpr {
+                op,
+                first,
+                second,
+                third,
+            } => Node::TernaryExpr {
+                op: op.clone(),
+                first: boxed(first),
+                second: boxed(second),
+                third: boxed(third),
+            },
+            Node::For {
+                var,
+                start,
+                end,
+                step,
+                body,
+            } => Node::For {
+                var: var.to_string(),
+                start: boxed(start),
+                end: boxed(end),
+                step: step.as_deref().map(boxed),
+                body: boxed(body),
+            },
+            Node::Statement { node, .. } => Node::Statement {
+                span: Span::default(),
+                node: boxed(node),
+            },
+            Node::Caller { name, args } => Node::Caller {
+                name: name.to_string(),
+                args: boxed(args),
+            },
+            // the other nodes have no statements in them
+            node => node.clone(),
+        }
+    }
+
+    ///
+    /// Returns whether two nodes are the same apart from
+    /// the spans of the statements in them
+    ///
+    pub fn eq_ignoring_spans(&self, other: &Node) -> bool {
+        let all_eq = |nodes: &[Node], others: &[Node]| {
+            nodes.len() == others.len()
+                && nodes
+                    .iter()
+                    .zip(others)
+                    .all(|(node, other)| node.eq_ignoring_spans(other))
+        };
+        match (self, other) {
+            (Node::List(nodes), Node::List(others)) | (Node::Body(nodes), Node::Body(others)) => {
+                all_eq(nodes, others)
+            }
+            (
+                Node::UnaryExpr { op, child },
+                Node::UnaryExpr {
+                    op: other_op,
+                    child: other_child,
+                },
+            ) => op == other_op && child.eq_ignoring_spans(other_child),
+            (
+                Node::BinaryExpr { op, lhs, rhs },
+                Node::BinaryExpr {
+                    op: other_op,
+                    lhs: other_lhs,
+                    rhs: other_rhs,
+                },
+            ) => {
+                op == other_op
+                    && lhs.eq_ignoring_spans(other_lhs)
+                    && rhs.eq_ignoring_spans(other_rhs)
+            }
+            (
+                Node::TernaryExpr {
+                    op,
+                    first,
+                    second,
+                    third,
+                },
+                Node::TernaryExpr {
+                    op: other_op,
+                    first: other_first,
+                    second: other_second,
+                    third: other_third,
+                },
+            ) => {
+                op == other_op
+                    && first.eq_ignoring_spans(other_first)
+                    && second.eq_ignoring_spans(other_second)
+                    && third.eq_ignoring_spans(other_third)
+            }
+            (
+                Node::For {
+                    var,
+                    start,
+                    end,
+                    step,
+                    body,
+                },
+                Node::For {
+                    var: other_var,
+                    start: other_start,
+                    end: other_end,
+                    step: other_step,
+                    body: other_body,
+                },
+            ) => {
+                let steps_eq = match (step, other_step) {
+                    (Some(step), Some(other_step)) => step.eq_ignoring_spans(other_step),
+                    (step, other_step) => step.is_none() && other_step.is_none(),
+                };
+                var == other_var
+                    && start.eq_ignoring_spans(other_start)
+                    && end.eq_ignoring_spans(other_end)
+                    && steps_eq
+                    && body.eq_ignoring_spans(other_body)
+            }
+            (
+                Node::Statement { node, .. },
+                Node::Statement {
+                    node: other_node, ..
+                },
+            ) => node.eq_ignoring_spans(other_node),
+            (
+                Node::Caller { name, args },
+                Node::Caller {
+                    name: other_name,
+                    args: other_args,
+                },
+            ) => name == other_name && args.eq_ignoring_spans(other_args),
+            // the other nodes have no statements in them
+            (node, other) => node == other,
+        }
+    }
+}
+
+///
+/// Struct representing a position in the logo code,
+/// where both the line and column count from 1
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
 ///
 /// Enum representing the possible leaf nodes
 /// In the AST, this is either a Literal or Variable
 ///
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Leaf {
     Literal,
     Variable,
 }
+
+///
+/// Enum representing where a variable reference is looked up
+/// Local variables belong to the innermost function call, whereas Dynamic
+/// variables are searched for through the active function calls (innermost
+/// first) before falling back to Global
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Scope {
+    Unresolved,
+    Local,
+    Dynamic,
+    Global,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenise_source;
+    use crate::parser::parse_program;
+    use std::collections::HashSet;
+
+    fn parse(source: &str) -> Node {
+        Node::Body(parse_program(tokenise_source(source).unwrap()).unwrap())
+    }
+
+    #[test]
+    fn parses_to_expected_ast() {
+        let expected = Node::Body(vec![Node::Statement {
+            span: Span { line: 1, column: 1 },
+            node: Box::new(Node::UnaryExpr {
+                op: UnaryOp::Forward,
+                child: Box::new(Node::BinaryExpr {
+                    op: BinaryOp::Add,
+                    lhs: Box::new(Node::Variable(String::from("x"), Scope::Unresolved)),
+                    rhs: Box::new(Node::Literal(String::from("10"))),
+                }),
+            }),
+        }]);
+        assert_eq!(parse("FORWARD + :x \"10"), expected);
+    }
+
+    #[test]
+    fn spans_are_only_ignored_when_asked() {
+        let first = parse("IF EQ \"1 \"1 [\n    PENUP\n]\n");
+        let second = parse("\n\nIF EQ \"1 \"1 [\nPENUP\n]\n");
+        assert_ne!(first, second);
+        assert!(first.eq_ignoring_spans(&second));
+        assert_eq!(first.without_spans(), second.without_spans());
+        assert!(!first.eq_ignoring_spans(&parse("IF EQ \"1 \"2 [\n    PENUP\n]\n")));
+    }
+
+    #[test]
+    fn every_child_is_compared() {
+        let sources = [
+            "FOR [i \"1 \"3] [\n    PRINT :i\n]\n",
+            "FOR [i \"1 \"3 \"1] [\n    PRINT :i\n]\n",
+            "FOR [i \"1 \"3] [\n    PRINT \"i\n]\n",
+            "IFELSE EQ \"1 \"1 [\n    PENUP\n] [\n    PENDOWN\n]\n",
+            "IFELSE EQ \"1 \"1 [\n    PENUP\n] [\n    PENUP\n]\n",
+            "PRINT [1 [2 3]]\n",
+            "PRINT [1 [2 4]]\n",
+        ];
+        for (index, source) in sources.iter().enumerate() {
+            for (other_index, other) in sources.iter().enumerate() {
+                let (node, other) = (parse(source), parse(&format!("\n{other}")));
+                assert_eq!(node.eq_ignoring_spans(&other), index == other_index);
+                assert_eq!(
+                    node.without_spans() == other.without_spans(),
+                    index == other_index
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn nodes_without_spans_hash_the_same() {
+        let mut seen = HashSet::new();
+        seen.insert(parse("PRINT \"1\nPENUP\n").without_spans());
+        assert!(seen.contains(&parse("\nPRINT \"1\n\nPENUP\n").without_spans()));
+        assert!(!seen.contains(&parse("PRINT \"2\nPENUP\n").without_spans()));
+    }
+}
@@ -0,0 +1,909 @@
+use crate::ast::*;
+use crate::lexer::tokenise_source;
+use crate::parser::{parse_program, KEYWORDS};
+use crate::printer::print_logo;
+use crate::visit::{walk_caller, Visitor};
+use std::collections::HashMap;
+
+///
+/// Struct representing an expression built for a Builder, which is either
+/// a node that outputs a value or the first error found while building it
+///
+/// Expressions are made with the functions of this module, e.g.
+/// add(var("x"), lit(1)), and any error is reported by the Builder
+/// once the expression is given to it
+///
+#[derive(Debug, Clone)]
+pub struct Expr(Result<Node, String>);
+
+impl Expr {
+    fn map(self, f: impl FnOnce(Node) -> Result<Node, String>) -> Expr {
+        Expr(self.0.and_then(f))
+    }
+}
+
+///
+/// A literal word, e.g. lit(10) is written as "10
+///
+pub fn lit(word: impl ToString) -> Expr {
+    let word = word.to_string();
+    match check_word(&word) {
+        Ok(()) => Expr(Ok(Node::Literal(word))),
+        Err(e) => Expr(Err(e)),
+    }
+}
+
+///
+/// The value of a variable, e.g. var("x") is written as :x
+///
+pub fn var(name: &str) -> Expr {
+    match check_name(name) {
+        Ok(()) => Expr(Ok(Node::Variable(name.to_string(), Scope::Unresolved))),
+        Err(e) => Expr(Err(e)),
+    }
+}
+
+///
+/// A data list of words, e.g. list([1, 2, 3]) is written as [1 2 3]
+///
+pub fn list<T: ToString>(items: impl IntoIterator<Item = T>) -> Expr {
+    let mut nodes = vec![];
+    for item in items {
+        let item = item.to_string();
+        if item.is_empty() {
+            return Expr(Err(String::from("List items cannot be empty")));
+        }
+        if let Err(e) = check_word(&item) {
+            return Expr(Err(e));
+        }
+        nodes.push(Node::Literal(item));
+    }
+    Expr(Ok(Node::List(nodes)))
+}
+
+///
+/// A node that outputs a value without taking any inputs, e.g. query(Node::Xcor)
+///
+pub fn query(node: Node) -> Expr {
+    match node {
+        Node::Xcor
+        | Node::Ycor
+        | Node::Heading
+        | Node::Pos
+        | Node::Pi
+        | Node::Color
+        | Node::Pensize
+        | Node::Pendownp => Expr(Ok(node)),
+        node => Expr(Err(format!("{node:?} does not output a value"))),
+    }
+}
+
+pub fn xcor() -> Expr {
+    query(Node::Xcor)
+}
+
+pub fn ycor() -> Expr {
+    query(Node::Ycor)
+}
+
+pub fn heading() -> Expr {
+    query(Node::Heading)
+}
+
+pub fn pos() -> Expr {
+    query(Node::Pos)
+}
+
+///
+/// An operation on one input that outputs a value, e.g. unary(UnaryOp::Sqrt, lit(2))
+///
+pub fn unary(op: UnaryOp, child: Expr) -> Expr {
+    if !op.reports() {
+        return Expr(Err(format!("{} does not output a value", op.keyword())));
+    }
+    child.map(|child| {
+        Ok(Node::UnaryExpr {
+            op,
+            child: Box::new(child),
+        })
+    })
+}
+
+///
+/// An operation on two inputs that outputs a value, e.g. binary(BinaryOp::Add, lit(1), lit(2))
+///
+pub fn binary(op: BinaryOp, lhs: Expr, rhs: Expr) -> Expr {
+    if !op.reports() {
+        return Expr(Err(format!("{} does not output a value", op.keyword())));
+    }
+    lhs.map(|lhs| {
+        rhs.0.map(|rhs| Node::BinaryExpr {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        })
+    })
+}
+
+pub fn add(lhs: Expr, rhs: Expr) -> Expr {
+    binary(BinaryOp::Add, lhs, rhs)
+}
+
+pub fn sub(lhs: Expr, rhs: Expr) -> Expr {
+    binary(BinaryOp::Sub, lhs, rhs)
+}
+
+pub fn mul(lhs: Expr, rhs: Expr) -> Expr {
+    binary(BinaryOp::Mul, lhs, rhs)
+}
+
+pub fn div(lhs: Expr, rhs: Expr) -> Expr {
+    binary(BinaryOp::Div, lhs, rhs)
+}
+
+pub fn eq(lhs: Expr, rhs: Expr) -> Expr {
+    binary(BinaryOp::Eq, lhs, rhs)
+}
+
+pub fn ne(lhs: Expr, rhs: Expr) -> Expr {
+    binary(BinaryOp::Ne, lhs, rhs)
+}
+
+pub fn gt(lhs: Expr, rhs: Expr) -> Expr {
+    binary(BinaryOp::Gt, lhs, rhs)
+}
+
+pub fn lt(lhs: Expr, rhs: Expr) -> Expr {
+    binary(BinaryOp::Lt, lhs, rhs)
+}
+
+pub fn and(lhs: Expr, rhs: Expr) -> Expr {
+    binary(BinaryOp::And, lhs, rhs)
+}
+
+pub fn or(lhs: Expr, rhs: Expr) -> Expr {
+    binary(BinaryOp::Or, lhs, rhs)
+}
+
+pub fn not(child: Expr) -> Expr {
+    unary(UnaryOp::Not, child)
+}
+
+///
+/// A call to a defined function that uses the value it outputs
+/// The Builder checks that the function is defined and takes that many inputs
+///
+pub fn call(name: &str, args: Vec<Expr>) -> Expr {
+    if let Err(e) = check_name(name) {
+        return Expr(Err(e));
+    }
+    let args: Result<Vec<Node>, String> = args.into_iter().map(|arg| arg.0).collect();
+    Expr(args.map(|args| Node::Caller {
+        name: name.to_string(),
+        args: Box::new(Node::Body(args)),
+    }))
+}
+
+///
+/// Checks that a word is read back as a single word by the lexer
+///
+fn check_word(word: &str) -> Result<(), String> {
+    if word.contains(char::is_whitespace) || word.contains(['[', ']']) {
+        return Err(format!("{word:?} cannot be written as a single word"));
+    }
+    Ok(())
+}
+
+///
+/// Checks that a name of a variable or function can be written in logo code
+///
+fn check_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.starts_with(['"', ':']) || name == "//" {
+        return Err(format!("{name:?} is not a valid name"));
+    }
+    check_word(name)
+}
+
+///
+/// This struct is responsible for building an AST statement by statement
+/// Every method adds a statement and gives the builder back, so programs
+/// can be written as a chain of calls, e.g.
+/// Builder::new().forward(lit(10)).make("x", add(var("x"), lit(1)))
+///
+/// Each statement is checked as it is added, e.g. that OUTPUT is inside a
+/// function, that a call gives each defined function the number of inputs
+/// it takes and that a call whose value is used is to a function with an
+/// OUTPUT. The first error is kept and returned by build
+///
+/// Note that function is the name of the function whose body is being built
+///
+#[derive(Debug, Default)]
+pub struct Builder {
+    statements: Vec<Node>,
+    functions: HashMap<String, Function>,
+    function: Option<String>,
+    error: Option<String>,
+}
+
+///
+/// Struct representing what the builder knows about a defined function,
+/// where outputs is whether its body has an OUTPUT, building is whether its
+/// body is still being built and used_in_body is whether its own body uses
+/// the value it outputs, which is only checked once the whole body is built
+///
+#[derive(Debug)]
+struct Function {
+    arity: usize,
+    outputs: bool,
+    building: bool,
+    used_in_body: bool,
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Builder::default()
+    }
+
+    ///
+    /// Returns the AST, which is parsed from the code the statements print as
+    /// so that the span of each statement is its position in that code
+    ///
+    pub fn build(self) -> Result<Vec<Node>, String> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        let source = print_logo(&self.statements);
+        match parse_program(tokenise_source(&source)?) {
+            Ok(ast) => Ok(ast),
+            Err(e) => Err(format!("Built code does not parse: {e}")),
+        }
+    }
+
+    ///
+    /// Returns the logo code of the statements
+    ///
+    pub fn source(self) -> Result<String, String> {
+        self.build().map(|ast| print_logo(&ast))
+    }
+
+    ///
+    /// Runs a command that takes one input, e.g. command(UnaryOp::Forward, lit(10))
+    ///
+    pub fn command(mut self, op: UnaryOp, child: Expr) -> Self {
+        match op {
+            UnaryOp::Output => return self.output(child),
+            UnaryOp::Local => {
+                self.fail(String::from("LOCAL takes a name, so use local instead"));
+                return self;
+            }
+            ref op if op.reports() => {
+                self.fail(format!(
+                    "{} outputs a value, which is not used",
+                    op.keyword()
+                ));
+                return self;
+            }
+            _ => {}
+        }
+        if let Some(child) = self.expr(child) {
+            self.push(Node::UnaryExpr {
+                op,
+                child: Box::new(child),
+            });
+        }
+        self
+    }
+
+    ///
+    /// Runs a command that takes two inputs, e.g. binary_command(BinaryOp::Setxy, lit(1), lit(2))
+    ///
+    pub fn binary_command(mut self, op: BinaryOp, lhs: Expr, rhs: Expr) -> Self {
+        if !matches!(
+            op,
+            BinaryOp::Make | BinaryOp::Addassign | BinaryOp::Setxy | BinaryOp::Arc
+        ) {
+            self.fail(format!("{} cannot be run as a command here", op.keyword()));
+            return self;
+        }
+        let (lhs, rhs) = (self.expr(lhs), self.expr(rhs));
+        if let (Some(lhs), Some(rhs)) = (lhs, rhs) {
+            self.push(Node::BinaryExpr {
+                op,
+                lhs: Box::new(lhs),
+                rhs: Box::new(rhs),
+            });
+        }
+        self
+    }
+
+    ///
+    /// Runs a command that takes no inputs, e.g. leaf(Node::Penup)
+    ///
+    pub fn leaf(mut self, node: Node) -> Self {
+        match node {
+            Node::Penup
+            | Node::Pendown
+            | Node::Penpaint
+            | Node::Penerase
+            | Node::Penreverse
+            | Node::Home
+            | Node::Clean
+            | Node::Clearscreen => self.push(node),
+            Node::Stop => return self.stop(),
+            node => self.fail(format!("{node:?} is not a command")),
+        }
+        self
+    }
+
+    pub fn forward(self, distance: Expr) -> Self {
+        self.command(UnaryOp::Forward, distance)
+    }
+
+    pub fn back(self, distance: Expr) -> Self {
+        self.command(UnaryOp::Back, distance)
+    }
+
+    pub fn left(self, distance: Expr) -> Self {
+        self.command(UnaryOp::Left, distance)
+    }
+
+    pub fn right(self, distance: Expr) -> Self {
+        self.command(UnaryOp::Right, distance)
+    }
+
+    pub fn turn(self, degrees: Expr) -> Self {
+        self.command(UnaryOp::Turn, degrees)
+    }
+
+    pub fn setheading(self, degrees: Expr) -> Self {
+        self.command(UnaryOp::Setheading, degrees)
+    }
+
+    pub fn setpencolor(self, colour: Expr) -> Self {
+        self.command(UnaryOp::Setpencolor, colour)
+    }
+
+    pub fn print(self, value: Expr) -> Self {
+        self.command(UnaryOp::Print, value)
+    }
+
+    pub fn penup(self) -> Self {
+        self.leaf(Node::Penup)
+    }
+
+    pub fn pendown(self) -> Self {
+        self.leaf(Node::Pendown)
+    }
+
+    pub fn make(self, name: &str, value: Expr) -> Self {
+        let name = match check_name(name) {
+            Ok(()) => lit(name),
+            Err(e) => Expr(Err(e)),
+        };
+        self.binary_command(BinaryOp::Make, name, value)
+    }
+
+    pub fn addassign(self, name: &str, value: Expr) -> Self {
+        let name = match check_name(name) {
+            Ok(()) => lit(name),
+            Err(e) => Expr(Err(e)),
+        };
+        self.binary_command(BinaryOp::Addassign, name, value)
+    }
+
+    ///
+    /// Declares a local variable, which can only be done inside a function
+    ///
+    pub fn local(mut self, name: &str) -> Self {
+        if self.function.is_none() {
+            self.fail(String::from("LOCAL can only be used inside a function"));
+        } else if let Err(e) = check_name(name) {
+            self.fail(e);
+        } else {
+            self.push(Node::UnaryExpr {
+                op: UnaryOp::Local,
+                child: Box::new(Node::Literal(name.to_string())),
+            });
+        }
+        self
+    }
+
+    pub fn localmake(mut self, name: &str, value: Expr) -> Self {
+        if self.function.is_none() {
+            self.fail(String::from("LOCALMAKE can only be used inside a function"));
+        } else if let Err(e) = check_name(name) {
+            self.fail(e);
+        } else if let Some(value) = self.expr(value) {
+            self.push(Node::BinaryExpr {
+                op: BinaryOp::Localmake,
+                lhs: Box::new(Node::Literal(name.to_string())),
+                rhs: Box::new(value),
+            });
+        }
+        self
+    }
+
+    pub fn output(mut self, value: Expr) -> Self {
+        if self.function.is_none() {
+            self.fail(String::from("OUTPUT can only be used inside a function"));
+        } else if let Some(value) = self.expr(value) {
+            self.push(Node::UnaryExpr {
+                op: UnaryOp::Output,
+                child: Box::new(value),
+            });
+            let function = self
+                .function
+                .as_ref()
+                .and_then(|name| self.functions.get_mut(name));
+            if let Some(function) = function {
+                function.outputs = true;
+            }
+        }
+        self
+    }
+
+    pub fn stop(mut self) -> Self {
+        if self.function.is_none() {
+            self.fail(String::from("STOP can only be used inside a function"));
+        } else {
+            self.push(Node::Stop);
+        }
+        self
+    }
+
+    ///
+    /// Runs the statements added by body when cond is TRUE
+    ///
+    pub fn if_(mut self, cond: Expr, body: impl FnOnce(Builder) -> Builder) -> Self {
+        let cond = self.expr(cond);
+        let body = self.block(body);
+        if let Some(cond) = cond {
+            self.push(Node::BinaryExpr {
+                op: BinaryOp::If,
+                lhs: Box::new(cond),
+                rhs: Box::new(body),
+            });
+        }
+        self
+    }
+
+    ///
+    /// Runs the statements added by then when cond is TRUE,
+    /// and the statements added by otherwise when it is FALSE
+    ///
+    pub fn if_else(
+        mut self,
+        cond: Expr,
+        then: impl FnOnce(Builder) -> Builder,
+        otherwise: impl FnOnce(Builder) -> Builder,
+    ) -> Self {
+        let cond = self.expr(cond);
+        let then = self.block(then);
+        let otherwise = self.block(otherwise);
+        if let Some(cond) = cond {
+            self.push(Node::TernaryExpr {
+                op: TernaryOp::IfElse,
+                first: Box::new(cond),
+                second: Box::new(then),
+                third: Box::new(otherwise),
+            });
+        }
+        self
+    }
+
+    ///
+    /// Runs the statements added by body for as long as cond is TRUE
+    ///
+    pub fn while_(mut self, cond: Expr, body: impl FnOnce(Builder) -> Builder) -> Self {
+        let cond = self.expr(cond);
+        let body = self.block(body);
+        if let Some(cond) = cond {
+            self.push(Node::BinaryExpr {
+                op: BinaryOp::While,
+                lhs: Box::new(cond),
+                rhs: Box::new(body),
+            });
+        }
+        self
+    }
+
+    ///
+    /// Runs the statements added by body with var set to each value from
+    /// start to end, where step is 1 (or -1 when counting down) if not given
+    ///
+    pub fn for_(
+        mut self,
+        var: &str,
+        start: Expr,
+        end: Expr,
+        step: Option<Expr>,
+        body: impl FnOnce(Builder) -> Builder,
+    ) -> Self {
+        if let Err(e) = check_name(var) {
+            self.fail(e);
+            return self;
+        }
+        let (start, end) = (self.expr(start), self.expr(end));
+        let step = match step {
+            Some(step) => match self.expr(step) {
+                Some(Node::Literal(value)) if value.parse::<f64>() == Ok(0.0) => {
+                    self.fail(String::from("For step cannot be zero"));
+                    return self;
+                }
+                Some(step) => Some(Box::new(step)),
+                None => return self,
+            },
+            None => None,
+        };
+        let body = self.block(body);
+        if let (Some(start), Some(end)) = (start, end) {
+            self.push(Node::For {
+                var: var.to_string(),
+                start: Box::new(start),
+                end: Box::new(end),
+                step,
+                body: Box::new(body),
+            });
+        }
+        self
+    }
+
+    ///
+    /// Defines a function with the statements added by body, which
+    /// can call itself as it is defined before its body is built
+    ///
+    pub fn to(
+        mut self,
+        name: &str,
+        params: &[&str],
+        body: impl FnOnce(Builder) -> Builder,
+    ) -> Self {
+        if self.function.is_some() {
+            self.fail(String::from("TO cannot be used inside a function"));
+            return self;
+        }
+        if let Err(e) = check_name(name) {
+            self.fail(e);
+            return self;
+        }
+        if self.functions.contains_key(name) {
+            self.fail(format!("{name} is already defined"));
+            return self;
+        }
+        // the formatter writes keywords in upper case whatever case they are in
+        if KEYWORDS
+            .iter()
+            .any(|keyword| keyword.eq_ignore_ascii_case(name))
+        {
+            self.fail(format!("{name} is a keyword, so it cannot name a function"));
+            return self;
+        }
+        for (index, param) in params.iter().enumerate() {
+            if let Err(e) = check_name(param) {
+                self.fail(e);
+                return self;
+            }
+            if params[..index].contains(param) {
+                self.fail(format!("{param} is a parameter of {name} more than once"));
+                return self;
+            }
+        }
+
+        let function = Function {
+            arity: params.len(),
+            outputs: false,
+            building: true,
+            used_in_body: false,
+        };
+        self.functions.insert(name.to_string(), function);
+        let outer = self.function.replace(name.to_string());
+        let body = self.block(body);
+        self.function = outer;
+
+        if let Some(function) = self.functions.get_mut(name) {
+            function.building = false;
+            if function.used_in_body && !function.outputs {
+                self.fail(format!("{name} does not output a value"));
+            }
+        }
+
+        let params = params
+            .iter()
+            .map(|param| Node::Variable(param.to_string(), Scope::Unresolved))
+            .collect();
+        self.push(Node::BinaryExpr {
+            op: BinaryOp::Func(name.to_string()),
+            lhs: Box::new(Node::Body(params)),
+            rhs: Box::new(body),
+        });
+        self
+    }
+
+    ///
+    /// Calls a defined function as a command
+    ///
+    pub fn call(mut self, name: &str, args: Vec<Expr>) -> Self {
+        let node = match call(name, args).0 {
+            Ok(node) => node,
+            Err(e) => {
+                self.fail(e);
+                return self;
+            }
+        };
+        let mut calls = CallChecker {
+            functions: &mut self.functions,
+            error: None,
+        };
+        // the value of the call itself is not used, unlike its args
+        if let Node::Caller { name, args } = &node {
+            calls.check(name, args, false);
+            walk_caller(&mut calls, name, args);
+        }
+        match calls.error {
+            Some(e) => self.fail(e),
+            None => self.push(node),
+        }
+        self
+    }
+
+    fn fail(&mut self, error: String) {
+        self.error.get_or_insert(error);
+    }
+
+    fn push(&mut self, node: Node) {
+        self.statements.push(Node::Statement {
+            span: Span::default(),
+            node: Box::new(node),
+        });
+    }
+
+    ///
+    /// Returns the node of an expression once every call in it
+    /// has been checked, or None after keeping its error
+    ///
+    fn expr(&mut self, expr: Expr) -> Option<Node> {
+        let node = match expr.0 {
+            Ok(node) => node,
+            Err(e) => {
+                self.fail(e);
+                return None;
+            }
+        };
+        let mut calls = CallChecker {
+            functions: &mut self.functions,
+            error: None,
+        };
+        calls.visit_node(&node);
+        match calls.error {
+            Some(e) => {
+                self.fail(e);
+                None
+            }
+            None => Some(node),
+        }
+    }
+
+    ///
+    /// Builds the statements of a codeblock with a builder that knows the
+    /// same functions, keeping any functions it defines and its error
+    ///
+    fn block(&mut self, body: impl FnOnce(Builder) -> Builder) -> Node {
+        let inner = Builder {
+            statements: vec![],
+            functions: std::mem::take(&mut self.functions),
+            function: self.function.clone(),
+            error: self.error.take(),
+        };
+        let inner = body(inner);
+        self.functions = inner.functions;
+        self.error = inner.error;
+        Node::Body(inner.statements)
+    }
+}
+
+///
+/// Struct representing a visitor that checks every call in an expression
+/// is to a defined function with the number of inputs it takes, and which
+/// outputs a value since the expression uses it
+///
+struct CallChecker<'a> {
+    functions: &'a mut HashMap<String, Function>,
+    error: Option<String>,
+}
+
+impl CallChecker<'_> {
+    fn check(&mut self, name: &str, args: &Node, uses_value: bool) {
+        let argc = match args {
+            Node::Body(args) => args.len(),
+            _ => 0,
+        };
+        let error = match self.functions.get_mut(name) {
+            Some(function) if function.arity != argc => {
+                format!(
+                    "{name} takes {} inputs but was given {argc}",
+                    function.arity
+                )
+            }
+            Some(function) if uses_value && !function.outputs => {
+                // a function can use its own value before its OUTPUT is added
+                if function.building {
+                    function.used_in_body = true;
+                    return;
+                }
+                format!("{name} does not output a value")
+            }
+            Some(_) => return,
+            None => format!("I don't know how to {name}"),
+        };
+        self.error.get_or_insert(error);
+    }
+}
+
+impl Visitor for CallChecker<'_> {
+    fn visit_caller(&mut self, name: &str, args: &Node) {
+        self.check(name, args, true);
+        walk_caller(self, name, args);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Vec<Node> {
+        parse_program(tokenise_source(source).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn builds_the_parsed_ast() {
+        let ast = Builder::new()
+            .make("x", lit(1))
+            .forward(lit(10))
+            .make("x", add(var("x"), lit(1)))
+            .while_(eq(var("x"), lit(2)), |b| b.right(lit(90)).make("x", lit(3)))
+            .build()
+            .unwrap();
+        let expected = parse(
+            "MAKE \"x \"1\nFORWARD \"10\nMAKE \"x + :x \"1\n\
+             WHILE EQ :x \"2 [\n    RIGHT \"90\n    MAKE \"x \"3\n]\n",
+        );
+        assert_eq!(ast, expected);
+    }
+
+    #[test]
+    fn builds_recursive_functions() {
+        let source = Builder::new()
+            .to("fact", &["n"], |b| {
+                b.if_else(
+                    lt(var("n"), lit(2)),
+                    |b| b.output(lit(1)),
+                    |b| b.output(mul(var("n"), call("fact", vec![sub(var("n"), lit(1))]))),
+                )
+            })
+            .print(call("fact", vec![lit(5)]))
+            .source()
+            .unwrap();
+        assert!(source.contains("PRINT fact \"5"), "{source}");
+
+        // the value is used before the OUTPUT is added
+        let ast = Builder::new()
+            .to("f", &["n"], |b| {
+                b.make("x", call("f", vec![var("n")])).output(lit(1))
+            })
+            .build();
+        assert!(ast.is_ok(), "{ast:?}");
+    }
+
+    #[test]
+    fn rejects_redefined_functions() {
+        let error = Builder::new()
+            .to("f", &["a"], |b| b.output(var("a")))
+            .to("f", &["a", "b"], |b| b.output(var("b")))
+            .print(call("f", vec![lit(1), lit(2)]))
+            .build()
+            .unwrap_err();
+        assert_eq!(error, "f is already defined");
+    }
+
+    #[test]
+    fn rejects_nested_functions() {
+        let error = Builder::new()
+            .to("f", &[], |b| b.to("g", &[], |b| b.forward(lit(1))))
+            .build()
+            .unwrap_err();
+        assert_eq!(error, "TO cannot be used inside a function");
+    }
+
+    #[test]
+    fn rejects_output_outside_function() {
+        let error = Builder::new().output(lit(1)).build().unwrap_err();
+        assert_eq!(error, "OUTPUT can only be used inside a function");
+    }
+
+    #[test]
+    fn rejects_calls_with_wrong_number_of_inputs() {
+        let error = Builder::new()
+            .to("f", &["a"], |b| b.output(var("a")))
+            .print(call("f", vec![]))
+            .build()
+            .unwrap_err();
+        assert_eq!(error, "f takes 1 inputs but was given 0");
+    }
+
+    #[test]
+    fn rejects_calls_to_undefined_functions() {
+        let error = Builder::new().call("g", vec![]).build().unwrap_err();
+        assert_eq!(error, "I don't know how to g");
+    }
+
+    #[test]
+    fn rejects_duplicate_parameters() {
+        let error = Builder::new()
+            .to("f", &["x", "x"], |b| b)
+            .build()
+            .unwrap_err();
+        assert_eq!(error, "x is a parameter of f more than once");
+    }
+
+    #[test]
+    fn rejects_using_the_value_of_functions_without_output() {
+        let error = Builder::new()
+            .to("h", &[], |b| b.stop())
+            .print(call("h", vec![]))
+            .build()
+            .unwrap_err();
+        assert_eq!(error, "h does not output a value");
+
+        let error = Builder::new()
+            .to("h", &["n"], |b| b.print(call("h", vec![var("n")])))
+            .build()
+            .unwrap_err();
+        assert_eq!(error, "h does not output a value");
+
+        // the value of a call run as a command is not used
+        let ast = Builder::new()
+            .to("h", &[], |b| b.stop())
+            .call("h", vec![])
+            .build();
+        assert!(ast.is_ok(), "{ast:?}");
+    }
+
+    #[test]
+    fn rejects_keywords_as_function_names_in_any_case() {
+        for name in ["FORWARD", "forward", "Forward"] {
+            let error = Builder::new().to(name, &[], |b| b).build().unwrap_err();
+            assert_eq!(
+                error,
+                format!("{name} is a keyword, so it cannot name a function")
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_words_that_cannot_be_written() {
+        let error = Builder::new().print(lit("a b")).build().unwrap_err();
+        assert_eq!(error, "\"a b\" cannot be written as a single word");
+        let error = Builder::new().make(":x", lit(1)).build().unwrap_err();
+        assert_eq!(error, "\":x\" is not a valid name");
+    }
+
+    #[test]
+    fn rejects_values_and_commands_in_the_wrong_place() {
+        let error = Builder::new()
+            .command(UnaryOp::Sqrt, lit(1))
+            .build()
+            .unwrap_err();
+        assert_eq!(error, "SQRT outputs a value, which is not used");
+        let error = Builder::new()
+            .print(unary(UnaryOp::Forward, lit(1)))
+            .build()
+            .unwrap_err();
+        assert_eq!(error, "FORWARD does not output a value");
+    }
+
+    #[test]
+    fn keeps_the_first_error() {
+        let error = Builder::new().stop().output(lit(1)).build().unwrap_err();
+        assert_eq!(error, "STOP can only be used inside a function");
+    }
+}
@@ -0,0 +1,203 @@
+use crate::ast::{BinaryOp, Node, Scope, Span, UnaryOp};
+use crate::interpreter::Value;
+use std::fmt::Write;
+
+///
+/// Enum representing a single instruction of the bytecode VM
+/// Instructions take their inputs from the top of the value stack
+/// (the last input on top) and push any value they output
+///
+/// Indices into the constants pool are used for literal values and for the
+/// names of variables and functions, and jumps go to an index in the chunk
+///
+#[derive(Debug, Clone)]
+pub enum Instruction {
+    /// Pushes the constant at the index
+    Constant(usize),
+    /// Pushes the value of the variable named by the constant at the index
+    Variable(usize, Scope),
+    /// Pushes the value of a node that takes no inputs, e.g. XCOR
+    Query(Node),
+    /// Runs a command that takes no inputs, e.g. PENUP
+    Leaf(Node),
+    /// Pops one value and pushes the value of the operation
+    Unary(UnaryOp),
+    /// Pops two values and pushes the value of the operation
+    Binary(BinaryOp),
+    /// Pops one value and runs the command with it
+    Command(UnaryOp),
+    /// Pops two values and runs the command with them
+    BinaryCommand(BinaryOp),
+    /// Pops a value that nothing uses, which is an error
+    Unused,
+    /// Fails with the message stored in the constant at the index
+    Error(usize),
+    Jump(usize),
+    /// Pops TRUE or FALSE and jumps when it is FALSE
+    JumpIfFalse(usize),
+    /// Defines the function at the index of the program's functions
+    Define(usize),
+    /// Pops argc values and calls the function named by the constant at
+    /// the index with them, where output is whether its value is used
+    Call {
+        name: usize,
+        argc: usize,
+        output: bool,
+    },
+    /// Pops a value and returns it from the current function
+    Output,
+    /// Returns from the current function, or ends the program at the top level
+    Stop,
+    /// Pops the start, end and step (when given) of a FOR loop and starts it
+    ForStart {
+        step: bool,
+    },
+    /// Sets the variable named by the constant at the index to the next
+    /// value of the innermost FOR loop, or ends it and jumps to exit
+    ForNext {
+        var: usize,
+        exit: usize,
+    },
+}
+
+///
+/// Struct representing a sequence of instructions, where spans stores
+/// the position of the statement each instruction was compiled from
+///
+#[derive(Debug, Clone, Default)]
+pub struct Chunk {
+    pub code: Vec<Instruction>,
+    pub spans: Vec<Option<Span>>,
+}
+
+///
+/// Enum representing a parameter of a compiled function, which is either
+/// written as "name or :name, or is worked out by the caller by running
+/// a chunk that outputs the name
+///
+#[derive(Debug, Clone)]
+pub enum Param {
+    Name(String),
+    Computed(Chunk),
+}
+
+///
+/// Struct representing a compiled defined function
+///
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<Param>,
+    pub chunk: Chunk,
+}
+
+///
+/// Struct representing a compiled program, where main is run first
+/// and the chunks of all functions share the constants pool
+///
+#[derive(Debug, Clone, Default)]
+pub struct Program {
+    pub constants: Vec<Value>,
+    pub main: Chunk,
+    pub functions: Vec<Function>,
+}
+
+impl Program {
+    ///
+    /// Returns the name stored in the constant at the index
+    ///
+    pub fn name(&self, index: usize) -> &str {
+        match &self.constants[index] {
+            Value::Word(name) => name,
+            Value::List(_) => "",
+        }
+    }
+}
+
+///
+/// This function is responsible for writing a program as readable text
+/// Each chunk is listed with the index, source line and column, and
+/// operands of every instruction, with constants shown after their index
+///
+pub fn disassemble(program: &Program) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "== main ==");
+    disassemble_chunk(program, &program.main, &mut out);
+
+    for function in &program.functions {
+        let params: Vec<String> = function
+            .params
+            .iter()
+            .enumerate()
+            .map(|(index, param)| match param {
+                Param::Name(name) => format!(":{name}"),
+                Param::Computed(_) => format!("<param {index}>"),
+            })
+            .collect();
+        let _ = writeln!(out, "\n== {} {} ==", function.name, params.join(" "));
+        disassemble_chunk(program, &function.chunk, &mut out);
+
+        for (index, param) in function.params.iter().enumerate() {
+            if let Param::Computed(chunk) = param {
+                let _ = writeln!(out, "\n== {} <param {index}> ==", function.name);
+                disassemble_chunk(program, chunk, &mut out);
+            }
+        }
+    }
+    out
+}
+
+fn disassemble_chunk(program: &Program, chunk: &Chunk, out: &mut String) {
+    let mut previous = None;
+    for (index, (instruction, span)) in chunk.code.iter().zip(&chunk.spans).enumerate() {
+        // only show the position when it changes, like a listing
+        let position = match span {
+            Some(span) if previous != Some((span.line, span.column)) => {
+                previous = Some((span.line, span.column));
+                format!("{}:{}", span.line, span.column)
+            }
+            Some(_) => String::from("|"),
+            None => String::from("-"),
+        };
+        let _ = writeln!(
+            out,
+            "{index:04} {position:>7}  {}",
+            disassemble_instruction(program, instruction)
+        );
+    }
+}
+
+fn disassemble_instruction(program: &Program, instruction: &Instruction) -> String {
+    let constant = |index: usize| format!("{index} ({})", program.constants[index]);
+    match instruction {
+        Instruction::Constant(index) => format!("CONSTANT {}", constant(*index)),
+        Instruction::Variable(index, scope) => {
+            format!("VARIABLE {} {scope:?}", constant(*index))
+        }
+        Instruction::Query(node) => format!("QUERY {}", format!("{node:?}").to_uppercase()),
+        Instruction::Leaf(node) => format!("LEAF {}", format!("{node:?}").to_uppercase()),
+        Instruction::Unary(op) => format!("UNARY {}", op.keyword()),
+        Instruction::Binary(op) => format!("BINARY {}", op.keyword()),
+        Instruction::Command(op) => format!("COMMAND {}", op.keyword()),
+        Instruction::BinaryCommand(op) => format!("BINARY_COMMAND {}", op.keyword()),
+        Instruction::Unused => String::from("UNUSED"),
+        Instruction::Error(index) => format!("ERROR {}", constant(*index)),
+        Instruction::Jump(target) => format!("JUMP {target:04}"),
+        Instruction::JumpIfFalse(target) => format!("JUMP_IF_FALSE {target:04}"),
+        Instruction::Define(index) => {
+            format!("DEFINE {index} ({})", program.functions[*index].name)
+        }
+        Instruction::Call { name, argc, output } => {
+            let kind = if *output { "CALL_OUTPUT" } else { "CALL" };
+            format!("{kind} {} {argc}", constant(*name))
+        }
+        Instruction::Output => String::from("OUTPUT"),
+        Instruction::Stop => String::from("STOP"),
+        Instruction::ForStart { step } => {
+            format!("FOR_START{}", if *step { " STEP" } else { "" })
+        }
+        Instruction::ForNext { var, exit } => {
+            format!("FOR_NEXT {} {exit:04}", constant(*var))
+        }
+    }
+}
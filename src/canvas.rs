@@ -0,0 +1,37 @@
+///
+/// Struct representing how a drawing is placed on a canvas
+/// The origin is where the turtle's (0, 0) is drawn, in canvas pixels from
+/// the top left corner, and defaults to the centre of the canvas
+/// The scale is the number of canvas pixels per turtle step
+///
+#[derive(Debug, Clone)]
+pub struct CanvasOptions {
+    pub width: u32,
+    pub height: u32,
+    pub origin: Option<(f64, f64)>,
+    pub scale: f64,
+}
+
+impl Default for CanvasOptions {
+    fn default() -> Self {
+        CanvasOptions {
+            width: 500,
+            height: 500,
+            origin: None,
+            scale: 1.0,
+        }
+    }
+}
+
+impl CanvasOptions {
+    ///
+    /// Converts a turtle position into canvas pixels
+    /// Note that the turtle's y grows upwards whereas the canvas' y grows downwards
+    ///
+    pub fn to_canvas(&self, (x, y): (f64, f64)) -> (f64, f64) {
+        let (origin_x, origin_y) = self
+            .origin
+            .unwrap_or((self.width as f64 / 2.0, self.height as f64 / 2.0));
+        (origin_x + x * self.scale, origin_y - y * self.scale)
+    }
+}
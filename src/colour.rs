@@ -0,0 +1,175 @@
+///
+/// Struct representing a colour as its red, green and blue components
+/// This is shared by everything that interprets SETPENCOLOR, SETBACKGROUND
+/// and COLOR so they all agree on what a colour means
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Colour {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+///
+/// The default 16 colour palette, in palette index order, along with the
+/// name each colour can be referred to by (e.g. SETPENCOLOR "red)
+///
+pub const PALETTE: [(&str, Colour); 16] = [
+    ("black", Colour::rgb(0, 0, 0)),
+    ("blue", Colour::rgb(0, 0, 255)),
+    ("green", Colour::rgb(0, 255, 0)),
+    ("cyan", Colour::rgb(0, 255, 255)),
+    ("red", Colour::rgb(255, 0, 0)),
+    ("magenta", Colour::rgb(255, 0, 255)),
+    ("yellow", Colour::rgb(255, 255, 0)),
+    ("white", Colour::rgb(255, 255, 255)),
+    ("brown", Colour::rgb(155, 96, 59)),
+    ("tan", Colour::rgb(197, 136, 18)),
+    ("forest", Colour::rgb(100, 162, 64)),
+    ("aqua", Colour::rgb(120, 187, 187)),
+    ("salmon", Colour::rgb(255, 149, 119)),
+    ("purple", Colour::rgb(144, 113, 208)),
+    ("orange", Colour::rgb(255, 163, 0)),
+    ("grey", Colour::rgb(183, 183, 183)),
+];
+
+impl Colour {
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Colour { r, g, b }
+    }
+
+    ///
+    /// Returns the palette colour at the given index
+    ///
+    pub fn from_index(index: usize) -> Option<Colour> {
+        PALETTE.get(index).map(|(_, colour)| *colour)
+    }
+
+    ///
+    /// Returns the palette colour with the given name, ignoring case
+    /// Note that "gray" is accepted as well as "grey"
+    ///
+    pub fn from_name(name: &str) -> Option<Colour> {
+        let name = name.to_lowercase();
+        let name = if name == "gray" {
+            "grey"
+        } else {
+            name.as_str()
+        };
+        PALETTE
+            .iter()
+            .find(|(palette_name, _)| *palette_name == name)
+            .map(|(_, colour)| *colour)
+    }
+
+    ///
+    /// Returns the colour written as a hex word, either #rrggbb or #rgb
+    ///
+    pub fn from_hex(hex: &str) -> Option<Colour> {
+        let digits = hex.strip_prefix('#')?;
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        match digits.len() {
+            6 => Some(Colour::rgb(
+                u8::from_str_radix(&digits[0..2], 16).ok()?,
+                u8::from_str_radix(&digits[2..4], 16).ok()?,
+                u8::from_str_radix(&digits[4..6], 16).ok()?,
+            )),
+            3 => {
+                let channel = |i: usize| u8::from_str_radix(&digits[i..i + 1], 16).map(|v| v * 17);
+                Some(Colour::rgb(
+                    channel(0).ok()?,
+                    channel(1).ok()?,
+                    channel(2).ok()?,
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    ///
+    /// Returns the colour for a word given to SETPENCOLOR, which can
+    /// be a palette index, a hex word or a palette name
+    /// An index can be written as any whole number, e.g. "6 or "6.0
+    ///
+    pub fn from_word(word: &str) -> Option<Colour> {
+        if word.starts_with('#') {
+            Colour::from_hex(word)
+        } else if let Ok(index) = word.parse::<f64>() {
+            if index.fract() == 0.0 && index >= 0.0 {
+                Colour::from_index(index as usize)
+            } else {
+                None
+            }
+        } else {
+            Colour::from_name(word)
+        }
+    }
+
+    ///
+    /// Returns the colour for the components of a [r g b] list
+    /// Each component must be a whole number from 0 to 255
+    ///
+    pub fn from_components(components: &[f64]) -> Option<Colour> {
+        let channel = |value: f64| {
+            if value.fract() == 0.0 && (0.0..=255.0).contains(&value) {
+                Some(value as u8)
+            } else {
+                None
+            }
+        };
+        match components {
+            [r, g, b] => Some(Colour::rgb(channel(*r)?, channel(*g)?, channel(*b)?)),
+            _ => None,
+        }
+    }
+
+    ///
+    /// Returns the index of this colour in the default palette, if it is in it
+    ///
+    pub fn palette_index(&self) -> Option<usize> {
+        PALETTE.iter().position(|(_, colour)| colour == self)
+    }
+
+    ///
+    /// Returns this colour as a #rrggbb hex word
+    ///
+    pub fn to_hex(&self) -> String {
+        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_each_form_of_colour() {
+        let red = Colour::rgb(255, 0, 0);
+        assert_eq!(Colour::from_word("4"), Some(red));
+        assert_eq!(Colour::from_word("4.0"), Some(red));
+        assert_eq!(Colour::from_word("#ff0000"), Some(red));
+        assert_eq!(Colour::from_word("#f00"), Some(red));
+        assert_eq!(Colour::from_word("Red"), Some(red));
+        assert_eq!(Colour::from_word("gray"), Colour::from_word("grey"));
+        assert_eq!(Colour::from_components(&[255.0, 0.0, 0.0]), Some(red));
+    }
+
+    #[test]
+    fn rejects_what_is_not_a_colour() {
+        for word in ["16", "-1", "4.5", "#ff00", "#gg0000", "pink", ""] {
+            assert_eq!(Colour::from_word(word), None, "{word}");
+        }
+        assert_eq!(Colour::from_components(&[256.0, 0.0, 0.0]), None);
+        assert_eq!(Colour::from_components(&[0.5, 0.0, 0.0]), None);
+        assert_eq!(Colour::from_components(&[0.0, 0.0]), None);
+    }
+
+    #[test]
+    fn writes_hex_and_finds_palette_index() {
+        assert_eq!(Colour::rgb(155, 96, 59).to_hex(), "#9b603b");
+        assert_eq!(Colour::rgb(155, 96, 59).palette_index(), Some(8));
+        assert_eq!(Colour::rgb(1, 2, 3).palette_index(), None);
+    }
+}
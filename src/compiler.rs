@@ -0,0 +1,379 @@
+use crate::ast::*;
+use crate::bytecode::{Chunk, Function, Instruction, Param, Program};
+use crate::interpreter::{data_value, Value, OUTPUT_OUTSIDE_FUNCTION};
+use std::collections::HashMap;
+
+type CompileResult<T> = Result<T, String>;
+
+///
+/// This function is responsible for compiling an AST into bytecode
+/// Statements compile to instructions that leave the value stack as they
+/// found it, whereas expressions compile to instructions that push one value
+///
+/// The instructions are laid out so that running the program with the VM
+/// does the same as running the AST with the Interpreter, so mistakes the
+/// Interpreter reports while running are compiled to Error instructions
+///
+/// An AST the parser could not have made, such as a call whose inputs or a
+/// function whose parameters or body are not a Body, is rejected instead
+///
+pub fn compile(ast: &[Node]) -> CompileResult<Program> {
+    let mut compiler = Compiler::default();
+    let mut main = Chunk::default();
+    for node in ast {
+        compiler.statement(&mut main, node)?;
+    }
+    compiler.emit(&mut main, Instruction::Stop);
+
+    Ok(Program {
+        constants: compiler.constants,
+        main,
+        functions: compiler.functions,
+    })
+}
+
+///
+/// Struct representing the state of the compiler, where span is the
+/// position of the statement being compiled
+///
+#[derive(Default)]
+struct Compiler {
+    constants: Vec<Value>,
+    words: HashMap<String, usize>,
+    functions: Vec<Function>,
+    span: Option<Span>,
+    in_function: bool,
+}
+
+impl Compiler {
+    fn emit(&self, chunk: &mut Chunk, instruction: Instruction) -> usize {
+        chunk.code.push(instruction);
+        chunk.spans.push(self.span);
+        chunk.code.len() - 1
+    }
+
+    ///
+    /// Points the jump at the given index to the next instruction
+    ///
+    fn patch(&self, chunk: &mut Chunk, jump: usize) {
+        let target = chunk.code.len();
+        match &mut chunk.code[jump] {
+            Instruction::Jump(to) | Instruction::JumpIfFalse(to) => *to = target,
+            Instruction::ForNext { exit, .. } => *exit = target,
+            _ => {}
+        }
+    }
+
+    ///
+    /// Returns the index of a constant, where the same word is only stored once
+    ///
+    fn constant(&mut self, value: Value) -> usize {
+        if let Value::Word(word) = &value {
+            if let Some(index) = self.words.get(word) {
+                return *index;
+            }
+            self.words.insert(word.to_string(), self.constants.len());
+        }
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+
+    fn word(&mut self, word: &str) -> usize {
+        self.constant(Value::Word(word.to_string()))
+    }
+
+    fn error(&mut self, chunk: &mut Chunk, message: String) {
+        let index = self.word(&message);
+        self.emit(chunk, Instruction::Error(index));
+    }
+
+    ///
+    /// Compiles a node that is run as a command
+    ///
+    fn statement(&mut self, chunk: &mut Chunk, node: &Node) -> CompileResult<()> {
+        match node {
+            Node::Statement { span, node } => {
+                let outer = self.span.replace(*span);
+                let res = self.statement(chunk, node);
+                self.span = outer;
+                return res;
+            }
+            Node::Body(nodes) => {
+                for node in nodes {
+                    self.statement(chunk, node)?;
+                }
+            }
+            Node::Empty | Node::Newline => {}
+            Node::Stop => {
+                self.emit(chunk, Instruction::Stop);
+            }
+            Node::UnaryExpr {
+                op: UnaryOp::Output,
+                child,
+            } => {
+                if !self.in_function {
+                    self.error(chunk, String::from(OUTPUT_OUTSIDE_FUNCTION));
+                } else {
+                    self.expression(chunk, child)?;
+                    self.emit(chunk, Instruction::Output);
+                }
+            }
+            Node::UnaryExpr { op, child } => {
+                self.expression(chunk, child)?;
+                self.emit(chunk, Instruction::Command(op.clone()));
+            }
+            Node::BinaryExpr {
+                op: BinaryOp::If,
+                lhs,
+                rhs,
+            } => {
+                self.expression(chunk, lhs)?;
+                let skip = self.emit(chunk, Instruction::JumpIfFalse(0));
+                self.statement(chunk, rhs)?;
+                self.patch(chunk, skip);
+            }
+            Node::BinaryExpr {
+                op: BinaryOp::While,
+                lhs,
+                rhs,
+            } => {
+                let start = chunk.code.len();
+                self.expression(chunk, lhs)?;
+                let exit = self.emit(chunk, Instruction::JumpIfFalse(0));
+                self.statement(chunk, rhs)?;
+                self.emit(chunk, Instruction::Jump(start));
+                self.patch(chunk, exit);
+            }
+            Node::BinaryExpr {
+                op: BinaryOp::Func(name),
+                lhs,
+                rhs,
+            } => {
+                let index = self.function(name, lhs, rhs)?;
+                self.emit(chunk, Instruction::Define(index));
+            }
+            Node::BinaryExpr { op, lhs, rhs } => {
+                self.expression(chunk, lhs)?;
+                self.expression(chunk, rhs)?;
+                self.emit(chunk, Instruction::BinaryCommand(op.clone()));
+            }
+            Node::TernaryExpr {
+                op: TernaryOp::IfElse,
+                first,
+                second,
+                third,
+            } => {
+                self.expression(chunk, first)?;
+                let otherwise = self.emit(chunk, Instruction::JumpIfFalse(0));
+                self.statement(chunk, second)?;
+                let end = self.emit(chunk, Instruction::Jump(0));
+                self.patch(chunk, otherwise);
+                self.statement(chunk, third)?;
+                self.patch(chunk, end);
+            }
+            Node::For {
+                var,
+                start,
+                end,
+                step,
+                body,
+            } => {
+                self.expression(chunk, start)?;
+                self.expression(chunk, end)?;
+                if let Some(step) = step {
+                    self.expression(chunk, step)?;
+                }
+                self.emit(
+                    chunk,
+                    Instruction::ForStart {
+                        step: step.is_some(),
+                    },
+                );
+                let var = self.word(var);
+                let next = self.emit(chunk, Instruction::ForNext { var, exit: 0 });
+                self.statement(chunk, body)?;
+                self.emit(chunk, Instruction::Jump(next));
+                self.patch(chunk, next);
+            }
+            Node::Caller { name, args } => self.call(chunk, name, args, false)?,
+            Node::Penup
+            | Node::Pendown
+            | Node::Penpaint
+            | Node::Penerase
+            | Node::Penreverse
+            | Node::Home
+            | Node::Clean
+            | Node::Clearscreen => {
+                self.emit(chunk, Instruction::Leaf(node.clone()));
+            }
+            _ => {
+                self.expression(chunk, node)?;
+                self.emit(chunk, Instruction::Unused);
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// Compiles a node that is evaluated for its value
+    ///
+    fn expression(&mut self, chunk: &mut Chunk, node: &Node) -> CompileResult<()> {
+        match node {
+            Node::Literal(word) => {
+                let index = self.word(word);
+                self.emit(chunk, Instruction::Constant(index));
+            }
+            Node::Variable(name, scope) => {
+                let index = self.word(name);
+                self.emit(chunk, Instruction::Variable(index, *scope));
+            }
+            Node::List(_) => {
+                let index = self.constant(data_value(node));
+                self.emit(chunk, Instruction::Constant(index));
+            }
+            Node::UnaryExpr { op, child } => {
+                if !op.reports() {
+                    self.error(chunk, format!("{} does not output a value", op.keyword()));
+                } else {
+                    self.expression(chunk, child)?;
+                    self.emit(chunk, Instruction::Unary(op.clone()));
+                }
+            }
+            Node::BinaryExpr { op, lhs, rhs } => {
+                if !op.reports() {
+                    self.error(chunk, format!("{} does not output a value", op.keyword()));
+                } else {
+                    self.expression(chunk, lhs)?;
+                    self.expression(chunk, rhs)?;
+                    self.emit(chunk, Instruction::Binary(op.clone()));
+                }
+            }
+            Node::Caller { name, args } => self.call(chunk, name, args, true)?,
+            Node::Statement { node, .. } => self.expression(chunk, node)?,
+            Node::TernaryExpr { op, .. } => {
+                self.error(chunk, format!("{} does not output a value", op.keyword()))
+            }
+            Node::For { .. } => self.error(chunk, String::from("FOR does not output a value")),
+            Node::Xcor
+            | Node::Ycor
+            | Node::Heading
+            | Node::Pos
+            | Node::Pi
+            | Node::Color
+            | Node::Pensize
+            | Node::Pendownp => {
+                self.emit(chunk, Instruction::Query(node.clone()));
+            }
+            _ => self.error(chunk, String::from("Not enough inputs")),
+        }
+        Ok(())
+    }
+
+    fn call(
+        &mut self,
+        chunk: &mut Chunk,
+        name: &str,
+        args: &Node,
+        output: bool,
+    ) -> CompileResult<()> {
+        let args = match args {
+            Node::Body(args) => args.as_slice(),
+            _ => return Err(format!("Invalid inputs to {name}")),
+        };
+        for arg in args {
+            self.expression(chunk, arg)?;
+        }
+        let name = self.word(name);
+        self.emit(
+            chunk,
+            Instruction::Call {
+                name,
+                argc: args.len(),
+                output,
+            },
+        );
+        Ok(())
+    }
+
+    ///
+    /// Compiles the body of a defined function into its own chunk,
+    /// returning the index of the function in the program
+    ///
+    fn function(&mut self, name: &str, params: &Node, body: &Node) -> CompileResult<usize> {
+        let params = match (params, body) {
+            (Node::Body(params), Node::Body(_)) => params.as_slice(),
+            _ => return Err(format!("Invalid definition of {name}")),
+        };
+        let mut compiled = vec![];
+        for param in params {
+            match param {
+                Node::Literal(name) | Node::Variable(name, _) => {
+                    compiled.push(Param::Name(name.to_string()))
+                }
+                // other parameters are run by the caller, which keeps its own position
+                param => {
+                    let mut chunk = Chunk::default();
+                    let outer = self.span.take();
+                    let res = self.expression(&mut chunk, param);
+                    self.emit(&mut chunk, Instruction::Output);
+                    self.span = outer;
+                    res?;
+                    compiled.push(Param::Computed(chunk));
+                }
+            }
+        }
+
+        let mut chunk = Chunk::default();
+        let outer = self.in_function;
+        self.in_function = true;
+        let res = self.statement(&mut chunk, body);
+        self.in_function = outer;
+        res?;
+        self.emit(&mut chunk, Instruction::Stop);
+
+        self.functions.push(Function {
+            name: name.to_string(),
+            params: compiled,
+            chunk,
+        });
+        Ok(self.functions.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statement(node: Node) -> Node {
+        Node::Statement {
+            span: Span::default(),
+            node: Box::new(node),
+        }
+    }
+
+    #[test]
+    fn rejects_asts_the_parser_would_not_make() {
+        let call = statement(Node::Caller {
+            name: String::from("f"),
+            args: Box::new(Node::Literal(String::from("1"))),
+        });
+        assert_eq!(compile(&[call]).unwrap_err(), "Invalid inputs to f");
+
+        let function = statement(Node::BinaryExpr {
+            op: BinaryOp::Func(String::from("f")),
+            lhs: Box::new(Node::Body(vec![])),
+            rhs: Box::new(Node::Stop),
+        });
+        assert_eq!(compile(&[function]).unwrap_err(), "Invalid definition of f");
+    }
+
+    #[test]
+    fn runtime_mistakes_compile_to_errors() {
+        let output = statement(Node::UnaryExpr {
+            op: UnaryOp::Output,
+            child: Box::new(Node::Literal(String::from("1"))),
+        });
+        let program = compile(&[output]).unwrap();
+        assert!(matches!(program.main.code[0], Instruction::Error(_)));
+    }
+}
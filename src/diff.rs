@@ -0,0 +1,188 @@
+use crate::ast::*;
+
+///
+/// Enum representing a difference between the statements of two versions
+/// of a program, where the indexes are positions in the list of statements
+/// of the old or new version and the nodes keep the span they were parsed with
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Change {
+    Inserted {
+        index: usize,
+        node: Node,
+    },
+    Removed {
+        index: usize,
+        node: Node,
+    },
+    Changed {
+        old_index: usize,
+        new_index: usize,
+        old: Node,
+        new: Node,
+    },
+}
+
+///
+/// This function is responsible for finding how the statements of a
+/// program changed between two versions of it
+/// Statements are compared ignoring spans, so moving a statement to another
+/// line by editing the code around it does not count as a change. The
+/// statements kept in both versions are found as their longest common
+/// subsequence, and the rest are reported in order of where they are
+///
+/// Note that a statement removed where another is inserted is reported as
+/// Changed, e.g. editing the body of a function changes the TO statement
+///
+pub fn diff(old: &[Node], new: &[Node]) -> Vec<Change> {
+    // kept[i][j] is the number of statements kept between old[i..] and new[j..]
+    let mut kept = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            kept[i][j] = if old[i].eq_ignoring_spans(&new[j]) {
+                kept[i + 1][j + 1] + 1
+            } else {
+                kept[i + 1][j].max(kept[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = vec![];
+    let (mut i, mut j) = (0, 0);
+    let (mut removed, mut inserted) = (vec![], vec![]);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i].eq_ignoring_spans(&new[j]) {
+            pair_up(&mut changes, &mut removed, &mut inserted, old, new);
+            i += 1;
+            j += 1;
+        } else if j == new.len() || (i < old.len() && kept[i + 1][j] >= kept[i][j + 1]) {
+            removed.push(i);
+            i += 1;
+        } else {
+            inserted.push(j);
+            j += 1;
+        }
+    }
+    pair_up(&mut changes, &mut removed, &mut inserted, old, new);
+    changes
+}
+
+///
+/// Reports the statements removed and inserted between two kept statements,
+/// pairing them up in order as changed statements
+///
+fn pair_up(
+    changes: &mut Vec<Change>,
+    removed: &mut Vec<usize>,
+    inserted: &mut Vec<usize>,
+    old: &[Node],
+    new: &[Node],
+) {
+    let paired = removed.len().min(inserted.len());
+    for (&old_index, &new_index) in removed.iter().zip(inserted.iter()) {
+        changes.push(Change::Changed {
+            old_index,
+            new_index,
+            old: old[old_index].clone(),
+            new: new[new_index].clone(),
+        });
+    }
+    for &index in &removed[paired..] {
+        changes.push(Change::Removed {
+            index,
+            node: old[index].clone(),
+        });
+    }
+    for &index in &inserted[paired..] {
+        changes.push(Change::Inserted {
+            index,
+            node: new[index].clone(),
+        });
+    }
+    removed.clear();
+    inserted.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenise_source;
+    use crate::parser::parse_program;
+
+    fn parse(source: &str) -> Vec<Node> {
+        parse_program(tokenise_source(source).unwrap()).unwrap()
+    }
+
+    ///
+    /// Writes each change as a sign with the indexes it is at
+    ///
+    fn summary(old: &str, new: &str) -> Vec<String> {
+        diff(&parse(old), &parse(new))
+            .iter()
+            .map(|change| match change {
+                Change::Inserted { index, .. } => format!("+{index}"),
+                Change::Removed { index, .. } => format!("-{index}"),
+                Change::Changed {
+                    old_index,
+                    new_index,
+                    ..
+                } => format!("~{old_index}:{new_index}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_program_has_no_changes() {
+        let source = "FORWARD \"10\nRIGHT \"90\n";
+        assert!(summary(source, source).is_empty());
+    }
+
+    #[test]
+    fn moved_but_identical_statements_are_not_changes() {
+        let old = "FORWARD \"10\nRIGHT \"90\n";
+        let new = "\n// turn after moving\nFORWARD \"10\n\n\n    RIGHT \"90\n";
+        assert!(summary(old, new).is_empty());
+    }
+
+    #[test]
+    fn finds_inserted_statements() {
+        let old = "FORWARD \"10\nRIGHT \"90\n";
+        let new = "PENDOWN\nFORWARD \"10\nRIGHT \"90\nPENUP\n";
+        assert_eq!(summary(old, new), ["+0", "+3"]);
+    }
+
+    #[test]
+    fn finds_removed_statements() {
+        let old = "PENDOWN\nFORWARD \"10\nRIGHT \"90\nPENUP\n";
+        let new = "FORWARD \"10\nPENUP\n";
+        assert_eq!(summary(old, new), ["-0", "-2"]);
+    }
+
+    #[test]
+    fn pairs_removed_and_inserted_statements_as_changed() {
+        let old = "FORWARD \"10\nRIGHT \"90\nPENUP\n";
+        let new = "FORWARD \"20\nRIGHT \"90\nPENUP\n";
+        assert_eq!(summary(old, new), ["~0:0"]);
+
+        // extra statements in a run are left as removed or inserted
+        let old = "HOME\nFORWARD \"10\nPENUP\n";
+        let new = "HOME\nFORWARD \"20\nBACK \"5\nLEFT \"5\nPENUP\n";
+        assert_eq!(summary(old, new), ["~1:1", "+2", "+3"]);
+        assert_eq!(summary(new, old), ["~1:1", "-2", "-3"]);
+    }
+
+    #[test]
+    fn changed_function_bodies_change_the_function() {
+        let old = "TO sq :a\n    OUTPUT * :a :a\nEND\nPRINT sq \"2\n";
+        let new = "TO sq :a\n    OUTPUT * :a \"2\nEND\nPRINT sq \"2\n";
+        let changes = diff(&parse(old), &parse(new));
+        assert_eq!(changes.len(), 1);
+        match &changes[0] {
+            Change::Changed { old, new, .. } => {
+                assert!(!old.eq_ignoring_spans(new));
+                assert!(matches!(old, Node::Statement { span, .. } if span.line == 1));
+            }
+            change => panic!("expected a changed function, found {change:?}"),
+        }
+    }
+}
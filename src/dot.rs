@@ -0,0 +1,209 @@
+use crate::ast::*;
+use crate::sexp::data_list;
+use std::fmt::Write;
+
+///
+/// This function is responsible for writing an AST as a Graphviz graph
+/// Every node is labelled with its keyword, or with its value for literals,
+/// variables and data lists, and has an edge to each of its children. The
+/// edges to the parts of a node that are not told apart by their own label,
+/// such as the condition of a WHILE, are labelled with the part they lead to
+///
+/// Note that Statement nodes are left out, so each statement
+/// hangs off the body it belongs to
+///
+pub fn to_dot(ast: &[Node]) -> String {
+    let mut graph = Graph::default();
+    let _ = writeln!(graph.out, "digraph ast {{");
+    let _ = writeln!(graph.out, "    node [shape=box, fontname=monospace];");
+    let root = graph.node("program");
+    for node in ast {
+        graph.child(root, node, None);
+    }
+    let _ = writeln!(graph.out, "}}");
+    graph.out
+}
+
+///
+/// Struct representing the graph written so far,
+/// where count is the number of nodes in it
+///
+#[derive(Default)]
+struct Graph {
+    out: String,
+    count: usize,
+}
+
+impl Graph {
+    ///
+    /// Adds a node with the given label, returning its id
+    ///
+    fn node(&mut self, label: &str) -> usize {
+        let id = self.count;
+        self.count += 1;
+        let _ = writeln!(self.out, "    n{id} [label=\"{}\"];", escape(label));
+        id
+    }
+
+    fn edge(&mut self, from: usize, to: usize, label: Option<&str>) {
+        match label {
+            Some(label) => {
+                let _ = writeln!(self.out, "    n{from} -> n{to} [label=\"{label}\"];");
+            }
+            None => {
+                let _ = writeln!(self.out, "    n{from} -> n{to};");
+            }
+        }
+    }
+
+    ///
+    /// Adds the AST node and everything below it as a child of parent
+    ///
+    fn child(&mut self, parent: usize, node: &Node, label: Option<&str>) {
+        if let Node::Statement { node, .. } = node {
+            return self.child(parent, node, label);
+        }
+        let id = self.node(&node_label(node));
+        self.edge(parent, id, label);
+
+        match node {
+            Node::UnaryExpr { child, .. } => self.child(id, child, None),
+            Node::BinaryExpr {
+                op: BinaryOp::Func(_),
+                lhs,
+                rhs,
+            } => {
+                let params = self.node("params");
+                self.edge(id, params, None);
+                if let Node::Body(nodes) = lhs.as_ref() {
+                    for node in nodes {
+                        self.child(params, node, None);
+                    }
+                }
+                self.child(id, rhs, None);
+            }
+            Node::BinaryExpr {
+                op: BinaryOp::If | BinaryOp::While,
+                lhs,
+                rhs,
+            } => {
+                self.child(id, lhs, Some("cond"));
+                self.child(id, rhs, None);
+            }
+            Node::BinaryExpr { lhs, rhs, .. } => {
+                self.child(id, lhs, None);
+                self.child(id, rhs, None);
+            }
+            Node::TernaryExpr {
+                first,
+                second,
+                third,
+                ..
+            } => {
+                self.child(id, first, Some("cond"));
+                self.child(id, second, Some("then"));
+                self.child(id, third, Some("else"));
+            }
+            Node::For {
+                start,
+                end,
+                step,
+                body,
+                ..
+            } => {
+                self.child(id, start, Some("start"));
+                self.child(id, end, Some("end"));
+                if let Some(step) = step {
+                    self.child(id, step, Some("step"));
+                }
+                self.child(id, body, None);
+            }
+            Node::Body(nodes) => {
+                for node in nodes {
+                    self.child(id, node, None);
+                }
+            }
+            Node::Caller { args, .. } => {
+                if let Node::Body(args) = args.as_ref() {
+                    for arg in args {
+                        self.child(id, arg, None);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn node_label(node: &Node) -> String {
+    match node {
+        Node::Literal(word) => format!("\"{word}"),
+        Node::Variable(name, _) => format!(":{name}"),
+        Node::List(_) => data_list(node),
+        Node::UnaryExpr { op, .. } => op.keyword().to_string(),
+        Node::BinaryExpr {
+            op: BinaryOp::Func(name),
+            ..
+        } => format!("TO {name}"),
+        Node::BinaryExpr { op, .. } => op.keyword().to_string(),
+        Node::TernaryExpr { op, .. } => op.keyword().to_string(),
+        Node::For { var, .. } => format!("FOR {var}"),
+        Node::Body(_) => String::from("body"),
+        Node::Caller { name, .. } => name.to_string(),
+        node => node.keyword().unwrap_or_default().to_string(),
+    }
+}
+
+///
+/// Escapes the characters that cannot be written as they are in a DOT string
+///
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenise_source;
+    use crate::parser::parse_program;
+
+    #[test]
+    fn writes_function_and_while() {
+        let source = "TO f :a\n    WHILE LT :a \"3 [\n        PRINT \"a\\\"b\n    ]\nEND\n";
+        let ast = parse_program(tokenise_source(source).unwrap()).unwrap();
+        let expected = r#"digraph ast {
+    node [shape=box, fontname=monospace];
+    n0 [label="program"];
+    n1 [label="TO f"];
+    n0 -> n1;
+    n2 [label="params"];
+    n1 -> n2;
+    n3 [label=":a"];
+    n2 -> n3;
+    n4 [label="body"];
+    n1 -> n4;
+    n5 [label="WHILE"];
+    n4 -> n5;
+    n6 [label="LT"];
+    n5 -> n6 [label="cond"];
+    n7 [label=":a"];
+    n6 -> n7;
+    n8 [label="\"3"];
+    n6 -> n8;
+    n9 [label="body"];
+    n5 -> n9;
+    n10 [label="PRINT"];
+    n9 -> n10;
+    n11 [label="\"a\\\"b"];
+    n10 -> n11;
+}
+"#;
+        assert_eq!(to_dot(&ast), expected);
+    }
+
+    #[test]
+    fn escapes_quotes_and_backslashes() {
+        assert_eq!(escape("\"a\"b"), "\\\"a\\\"b");
+        assert_eq!(escape("a\\b"), "a\\\\b");
+    }
+}
@@ -0,0 +1,192 @@
+use crate::ast::Node;
+use crate::lexer::{tokenise_source, Token};
+use crate::parser::{parse_program, KEYWORDS};
+use crate::printer::{print_with_layout, Layout};
+use std::collections::HashSet;
+
+///
+/// This function is responsible for formatting logo code
+/// The code is parsed and printed back with the printer, so every statement
+/// is on its own line with each codeblock indented by four spaces, and
+/// keywords are written in upper case with their full name, e.g. cs or
+/// setbg become CLEARSCREEN and SETBACKGROUND
+///
+/// Note that comments are kept at the indentation of the statements
+/// around them, and any run of blank lines becomes a single blank line
+///
+/// Note that the formatted code is parsed again and must give the same AST
+/// as the code did, otherwise formatting fails rather than change what the
+/// code does. Code that only parses once its keywords are in upper case is
+/// compared with the AST of the code with its keywords in upper case
+///
+pub fn format_logo(source: &str) -> Result<String, String> {
+    let tokens = tokenise_source(source)?;
+    let mut normalised = tokens.clone();
+    normalise_keywords(&mut normalised);
+    let layout = layout(source, &normalised);
+    let ast = parse_program(normalised)?;
+    let formatted = print_with_layout(&ast, layout);
+
+    let expected = parse_program(tokens).unwrap_or(ast);
+    let reparsed = match parse_program(tokenise_source(&formatted)?) {
+        Ok(reparsed) => reparsed,
+        Err(e) => return Err(format!("Formatted code does not parse: {e}")),
+    };
+    if !Node::Body(reparsed).eq_ignoring_spans(&Node::Body(expected)) {
+        return Err(String::from("Formatting would change what the code does"));
+    }
+    Ok(formatted)
+}
+
+///
+/// Finds the comments, blank lines and closing brackets of the code,
+/// skipping over the words inside comments and words written after " or :
+///
+fn layout(source: &str, tokens: &[Token]) -> Layout {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut layout = Layout::default();
+    for (index, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            layout.blank_lines.insert(index + 1);
+        }
+    }
+
+    let mut in_comment = false;
+    let mut previous = "";
+    for token in tokens {
+        match token.as_str() {
+            "\n" => in_comment = false,
+            _ if in_comment => {}
+            "//" if !matches!(previous, "\"" | ":") => {
+                in_comment = true;
+                let line = lines[token.span.line - 1];
+                // columns count characters rather than bytes
+                let comment: String = line.chars().skip(token.span.column - 1).collect();
+                layout
+                    .comments
+                    .push_back((token.span.line, comment.trim_end().to_string()));
+            }
+            "]" => layout.closers.push(token.span.line),
+            "END" if !matches!(previous, "\"" | ":") => layout.closers.push(token.span.line),
+            _ => {}
+        }
+        previous = token.as_str();
+    }
+    layout
+}
+
+///
+/// Writes every keyword in upper case, apart from words in comments, words
+/// written after " or :, the names of defined functions wherever they are
+/// used and the items of data lists, which are the brackets that are closed
+/// on the line they are opened on apart from the range of a FOR loop
+///
+fn normalise_keywords(tokens: &mut [Token]) {
+    let functions = function_names(tokens);
+    let mut in_comment = false;
+    let mut lists = 0;
+    let mut previous = String::new();
+    for index in 0..tokens.len() {
+        match tokens[index].as_str() {
+            "\n" => in_comment = false,
+            _ if in_comment => {}
+            "//" if !matches!(previous.as_str(), "\"" | ":") => in_comment = true,
+            "[" if lists > 0 || (previous != "FOR" && closes_on_line(tokens, index)) => lists += 1,
+            "]" if lists > 0 => lists -= 1,
+            word if functions.contains(word) => {}
+            word if lists == 0 && !matches!(previous.as_str(), "\"" | ":" | "TO") => {
+                let upper = word.to_uppercase();
+                if KEYWORDS.contains(&upper.as_str()) {
+                    tokens[index].value = upper;
+                }
+            }
+            _ => {}
+        }
+        previous = tokens[index].value.to_string();
+    }
+}
+
+///
+/// Returns the names of the functions defined by the code, which are the
+/// words after TO in any case outside of comments
+///
+fn function_names(tokens: &[Token]) -> HashSet<String> {
+    let mut names = HashSet::new();
+    let mut in_comment = false;
+    let mut previous = String::new();
+    for token in tokens {
+        match token.as_str() {
+            "\n" => in_comment = false,
+            _ if in_comment => {}
+            "//" if !matches!(previous.as_str(), "\"" | ":") => in_comment = true,
+            word if previous.eq_ignore_ascii_case("TO") => {
+                names.insert(word.to_string());
+            }
+            _ => {}
+        }
+        previous = token.value.to_string();
+    }
+    names
+}
+
+///
+/// Returns whether the bracket at the index is closed on the same line
+///
+fn closes_on_line(tokens: &[Token], index: usize) -> bool {
+    let mut depth = 0;
+    for token in &tokens[index..] {
+        match token.as_str() {
+            "[" => depth += 1,
+            "]" => {
+                depth -= 1;
+                if depth == 0 {
+                    return true;
+                }
+            }
+            "\n" => return false,
+            _ => {}
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_formats(source: &str, expected: &str) {
+        let formatted = format_logo(source).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(format_logo(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn upper_cases_keywords() {
+        assert_formats(
+            "forward \"10\nsetbg \"1\n",
+            "FORWARD \"10\nSETBACKGROUND \"1\n",
+        );
+    }
+
+    #[test]
+    fn keeps_comments() {
+        assert_formats(
+            "// draw\nforward \"10\n\n\n// turn\nright \"90\n",
+            "// draw\nFORWARD \"10\n\n// turn\nRIGHT \"90\n",
+        );
+    }
+
+    #[test]
+    fn slashes_after_quote_are_not_a_comment() {
+        assert_formats("PRINT \"//\n", "PRINT \"//\n");
+        assert_formats("MAKE \"x \"//\nPRINT :x\n", "MAKE \"x \"//\nPRINT :x\n");
+    }
+
+    #[test]
+    fn keeps_calls_to_functions_named_like_keywords() {
+        let source = "TO forward\n    PRINT \"hi\nEND\nforward\n";
+        assert_formats(source, source);
+        let source = "TO home :n\n    PRINT :n\nEND\nhome \"2\nHOME\n";
+        assert_formats(source, source);
+    }
+}
@@ -0,0 +1,1533 @@
+use crate::ast::*;
+use crate::colour::{Colour, PALETTE};
+use crate::output::OutputChannel;
+use crate::resolver::ScopingModel;
+use std::{
+    collections::HashMap,
+    fmt,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+type RuntimeResult<T> = Result<T, RuntimeError>;
+
+/// Arcs and circles are drawn as straight segments spanning this many degrees
+const ARC_STEP_DEGREES: f64 = 3.0;
+
+/// The background colour of a drawing until SETBACKGROUND is used
+pub const DEFAULT_BACKGROUND: Colour = PALETTE[0].1;
+
+///
+/// Struct representing an error raised while running a program
+/// The span is the position of the innermost statement being run
+///
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    pub message: String,
+    pub span: Option<Span>,
+    pub kind: ErrorKind,
+}
+
+///
+/// Enum representing why a program was stopped, where Program is a
+/// mistake in the program itself and the others are a limit being reached
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Program,
+    StepLimit,
+    DepthLimit,
+    SegmentLimit,
+    Timeout,
+}
+
+/// The number of nested function calls allowed unless other limits are given
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+
+/// How many steps are run between checks of the timeout
+const TIMEOUT_CHECK_INTERVAL: u64 = 1024;
+
+/// A function call gets a new stack segment when less than this much of the
+/// stack is left, which is enough for the nodes run before the next call
+const STACK_RED_ZONE: usize = 1024 * 1024;
+
+/// Size of each stack segment allocated for deeply nested function calls
+const STACK_SEGMENT_SIZE: usize = 16 * 1024 * 1024;
+
+///
+/// Struct representing the limits put on a running program so that
+/// programs that never finish are stopped, where None means no limit
+///
+/// Steps are counted for every node the Interpreter runs or evaluates, for
+/// every instruction the VM runs and for every straight piece of an arc or
+/// circle. Depth is the number of function calls running at once. Segments
+/// are the lines drawn, where every straight piece of an arc or circle
+/// counts as one
+///
+/// Note that the Interpreter uses the Rust stack for function calls, moving
+/// onto stack segments on the heap when it runs low, so a large max_depth
+/// can use a lot of memory
+///
+/// Note that the default only limits the depth, to DEFAULT_MAX_DEPTH, and
+/// leaves the steps, segments and time unlimited, so a program that loops
+/// forever without calling a function runs until it is stopped
+///
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub max_steps: Option<u64>,
+    pub max_depth: Option<usize>,
+    pub max_segments: Option<usize>,
+    pub timeout: Option<Duration>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_steps: None,
+            max_depth: Some(DEFAULT_MAX_DEPTH),
+            max_segments: None,
+            timeout: None,
+        }
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.span {
+            Some(span) => write!(f, "{}:{}: {}", span.line, span.column, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+///
+/// Enum representing the values a program works with
+/// Numbers and the booleans TRUE and FALSE are both kinds of words
+///
+#[derive(Debug, Clone)]
+pub enum Value {
+    Word(String),
+    List(Vec<Value>),
+}
+
+impl Value {
+    pub fn number(n: f64) -> Value {
+        if n.fract() == 0.0 && n.abs() < 1e15 {
+            Value::Word(format!("{}", n as i64))
+        } else {
+            Value::Word(format!("{n}"))
+        }
+    }
+
+    pub fn boolean(b: bool) -> Value {
+        Value::Word(String::from(if b { "TRUE" } else { "FALSE" }))
+    }
+
+    pub fn as_number(&self) -> Option<f64> {
+        match self {
+            Value::Word(word) => word.parse::<f64>().ok().filter(|n| n.is_finite()),
+            Value::List(_) => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Word(word) if word.eq_ignore_ascii_case("TRUE") => Some(true),
+            Value::Word(word) if word.eq_ignore_ascii_case("FALSE") => Some(false),
+            _ => None,
+        }
+    }
+
+    ///
+    /// Returns how PRINT and TYPE write this value,
+    /// which leaves off the outer brackets of a list
+    ///
+    pub fn to_print_string(&self) -> String {
+        match self {
+            Value::Word(word) => word.to_string(),
+            Value::List(items) => items
+                .iter()
+                .map(Value::to_string)
+                .collect::<Vec<String>>()
+                .join(" "),
+        }
+    }
+
+    ///
+    /// Compares two values the way EQ does, where
+    /// two numbers are equal if they have the same value
+    ///
+    pub fn equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::List(a), Value::List(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.equals(b))
+            }
+            (Value::Word(a), Value::Word(b)) => match (self.as_number(), other.as_number()) {
+                (Some(a), Some(b)) => a == b,
+                _ => a == b,
+            },
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    ///
+    /// Writes the value the way SHOW does, keeping the brackets of lists
+    ///
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Word(word) => write!(f, "{word}"),
+            Value::List(_) => write!(f, "[{}]", self.to_print_string()),
+        }
+    }
+}
+
+///
+/// Enum representing what the pen does to the lines it draws over
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PenMode {
+    Paint,
+    Erase,
+    Reverse,
+}
+
+///
+/// Struct representing the state of the turtle
+/// The turtle starts at the origin facing up (heading 0) with its pen up,
+/// where x grows to the right, y grows upwards and headings grow clockwise
+///
+#[derive(Debug, Clone)]
+pub struct Turtle {
+    pub x: f64,
+    pub y: f64,
+    pub heading: f64,
+    pub pen_down: bool,
+    pub colour: Colour,
+    pub pen_size: f64,
+    pub pen_mode: PenMode,
+}
+
+impl Default for Turtle {
+    fn default() -> Self {
+        Turtle {
+            x: 0.0,
+            y: 0.0,
+            heading: 0.0,
+            pen_down: false,
+            colour: PALETTE[7].1,
+            pen_size: 1.0,
+            pen_mode: PenMode::Paint,
+        }
+    }
+}
+
+///
+/// Struct representing a straight line drawn by the turtle
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub from: (f64, f64),
+    pub to: (f64, f64),
+    pub colour: Colour,
+    pub width: f64,
+    pub mode: PenMode,
+}
+
+///
+/// Enum representing the drawing operations produced by running a program
+/// Clear erases everything drawn before it
+///
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawOp {
+    Line(Line),
+    Clear,
+}
+
+///
+/// Struct representing everything a program has drawn
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Drawing {
+    pub background: Colour,
+    pub ops: Vec<DrawOp>,
+}
+
+impl Default for Drawing {
+    fn default() -> Self {
+        Drawing {
+            background: DEFAULT_BACKGROUND,
+            ops: vec![],
+        }
+    }
+}
+
+impl Drawing {
+    ///
+    /// Returns the lines that are still visible, i.e. those
+    /// drawn after the last Clear
+    ///
+    pub fn visible_lines(&self) -> impl Iterator<Item = &Line> {
+        let start = self
+            .ops
+            .iter()
+            .rposition(|op| matches!(op, DrawOp::Clear))
+            .map_or(0, |i| i + 1);
+        self.ops[start..].iter().filter_map(|op| match op {
+            DrawOp::Line(line) => Some(line),
+            DrawOp::Clear => None,
+        })
+    }
+}
+
+///
+/// Struct representing a defined function
+///
+#[derive(Debug)]
+struct Function {
+    params: Vec<Node>,
+    body: Vec<Node>,
+}
+
+///
+/// Enum representing how running a statement finished
+///
+enum Flow {
+    Next,
+    Output(Value),
+    Stop,
+}
+
+///
+/// Struct representing everything a running program can change apart
+/// from its defined functions, along with the operations on values
+///
+/// This is shared by the Interpreter and the bytecode VM so that both
+/// give the same results, where each of them works out the inputs of an
+/// operation before handing the values over
+///
+pub(crate) struct State<'a> {
+    pub(crate) output: &'a mut dyn OutputChannel,
+    pub(crate) scoping: ScopingModel,
+    pub(crate) turtle: Turtle,
+    pub(crate) drawing: Drawing,
+    pub(crate) globals: HashMap<String, Value>,
+    pub(crate) frames: Vec<HashMap<String, Option<Value>>>,
+    pub(crate) span: Option<Span>,
+    pub(crate) seed: u64,
+    pub(crate) limits: Limits,
+    steps: u64,
+    segments: usize,
+    started: Instant,
+}
+
+///
+/// This struct is responsible for running an AST, moving the turtle
+/// and recording the lines it draws
+///
+/// Variables are looked up using the Scope recorded by the resolver, and
+/// variables that were never resolved follow the given scoping model
+///
+pub struct Interpreter<'a> {
+    state: State<'a>,
+    functions: HashMap<String, Rc<Function>>,
+}
+
+impl<'a> Interpreter<'a> {
+    ///
+    /// Creates an interpreter that sends PRINT, SHOW and TYPE to output
+    ///
+    pub fn new(output: &'a mut dyn OutputChannel) -> Self {
+        Interpreter {
+            state: State::new(output),
+            functions: HashMap::new(),
+        }
+    }
+
+    pub fn with_scoping(mut self, scoping: ScopingModel) -> Self {
+        self.state.scoping = scoping;
+        self
+    }
+
+    ///
+    /// Sets the seed used by RANDOM, which otherwise
+    /// gives the same numbers on every run
+    ///
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.state.set_seed(seed);
+        self
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.state.limits = limits;
+        self
+    }
+
+    pub fn turtle(&self) -> &Turtle {
+        &self.state.turtle
+    }
+
+    pub fn drawing(&self) -> &Drawing {
+        &self.state.drawing
+    }
+
+    pub fn into_drawing(self) -> Drawing {
+        self.state.drawing
+    }
+
+    ///
+    /// Runs each statement of the AST in order
+    ///
+    pub fn run(&mut self, ast: &[Node]) -> RuntimeResult<()> {
+        self.state.start();
+        self.exec_block(ast).map(|_| ())
+    }
+
+    fn exec_block(&mut self, nodes: &[Node]) -> RuntimeResult<Flow> {
+        for node in nodes {
+            match self.exec(node)? {
+                Flow::Next => continue,
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    ///
+    /// Runs a node as a command
+    ///
+    fn exec(&mut self, node: &Node) -> RuntimeResult<Flow> {
+        self.state.step()?;
+        match node {
+            Node::Statement { span, node } => {
+                let outer = self.state.span.replace(*span);
+                let res = self.exec(node);
+                self.state.span = outer;
+                res
+            }
+            Node::Body(nodes) => self.exec_block(nodes),
+            Node::Empty | Node::Newline => Ok(Flow::Next),
+            Node::Stop => Ok(Flow::Stop),
+            Node::UnaryExpr {
+                op: UnaryOp::Output,
+                child,
+            } => {
+                if self.state.frames.is_empty() {
+                    return Err(self.state.error(String::from(OUTPUT_OUTSIDE_FUNCTION)));
+                }
+                Ok(Flow::Output(self.eval(child)?))
+            }
+            Node::UnaryExpr { op, child } => {
+                let value = self.eval(child)?;
+                self.state.command(op, value)?;
+                Ok(Flow::Next)
+            }
+            Node::BinaryExpr {
+                op: BinaryOp::If,
+                lhs,
+                rhs,
+            } => {
+                if self.eval_bool(lhs)? {
+                    return self.exec(rhs);
+                }
+                Ok(Flow::Next)
+            }
+            Node::BinaryExpr {
+                op: BinaryOp::While,
+                lhs,
+                rhs,
+            } => {
+                while self.eval_bool(lhs)? {
+                    match self.exec(rhs)? {
+                        Flow::Next => continue,
+                        flow => return Ok(flow),
+                    }
+                }
+                Ok(Flow::Next)
+            }
+            Node::BinaryExpr {
+                op: BinaryOp::Func(name),
+                lhs,
+                rhs,
+            } => {
+                let params = match lhs.as_ref() {
+                    Node::Body(params) => params.clone(),
+                    _ => vec![],
+                };
+                let body = match rhs.as_ref() {
+                    Node::Body(body) => body.clone(),
+                    _ => vec![],
+                };
+                self.functions
+                    .insert(name.to_string(), Rc::new(Function { params, body }));
+                Ok(Flow::Next)
+            }
+            Node::BinaryExpr { op, lhs, rhs } => {
+                let a = self.eval(lhs)?;
+                let b = self.eval(rhs)?;
+                self.state.binary_command(op, a, b)?;
+                Ok(Flow::Next)
+            }
+            Node::TernaryExpr {
+                op: TernaryOp::IfElse,
+                first,
+                second,
+                third,
+            } => {
+                if self.eval_bool(first)? {
+                    self.exec(second)
+                } else {
+                    self.exec(third)
+                }
+            }
+            Node::For {
+                var,
+                start,
+                end,
+                step,
+                body,
+            } => self.exec_for(var, start, end, step.as_deref(), body),
+            Node::Caller { name, args } => {
+                // any value passed to OUTPUT is not used
+                self.call(name, args)?;
+                Ok(Flow::Next)
+            }
+            _ => {
+                if self.state.exec_leaf(node)? {
+                    return Ok(Flow::Next);
+                }
+                let value = self.eval(node)?;
+                Err(self.state.unused(value))
+            }
+        }
+    }
+
+    fn exec_for(
+        &mut self,
+        var: &str,
+        start: &Node,
+        end: &Node,
+        step: Option<&Node>,
+        body: &Node,
+    ) -> RuntimeResult<Flow> {
+        let start = self.eval_number(start)?;
+        let end = self.eval_number(end)?;
+        let step = match step {
+            Some(step) => Some(self.eval_number(step)?),
+            None => None,
+        };
+        let step = self.state.for_step(start, end, step)?;
+
+        // each value is worked out from the start so that steps do not add up errors
+        let mut i = 0.0;
+        loop {
+            let value = start + i * step;
+            if for_finished(value, end, step) {
+                break;
+            }
+            self.state.set_for_var(var, value);
+            match self.exec(body)? {
+                Flow::Next => i += 1.0,
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    ///
+    /// Evaluates a node as an expression
+    ///
+    fn eval(&mut self, node: &Node) -> RuntimeResult<Value> {
+        self.state.step()?;
+        match node {
+            Node::Literal(word) => Ok(Value::Word(word.to_string())),
+            Node::Variable(name, scope) => self.state.lookup(name, *scope),
+            Node::List(_) => Ok(data_value(node)),
+            Node::UnaryExpr { op, child } => {
+                if !op.reports() {
+                    return Err(self.state.no_output(op.keyword()));
+                }
+                let value = self.eval(child)?;
+                self.state.unary(op, value)
+            }
+            Node::BinaryExpr { op, lhs, rhs } => {
+                if !op.reports() {
+                    return Err(self.state.no_output(op.keyword()));
+                }
+                let a = self.eval(lhs)?;
+                let b = self.eval(rhs)?;
+                self.state.binary(op, a, b)
+            }
+            Node::Caller { name, args } => match self.call(name, args)? {
+                Some(value) => Ok(value),
+                None => Err(self.state.error(format!("{name} did not output a value"))),
+            },
+            Node::Statement { node, .. } => self.eval(node),
+            Node::TernaryExpr { op, .. } => Err(self.state.no_output(op.keyword())),
+            Node::For { .. } => Err(self.state.no_output("FOR")),
+            _ => match self.state.query(node) {
+                Some(value) => Ok(value),
+                None => Err(self.state.error(String::from("Not enough inputs"))),
+            },
+        }
+    }
+
+    ///
+    /// Calls a defined function, returning the value it passed to OUTPUT
+    ///
+    fn call(&mut self, name: &str, args: &Node) -> RuntimeResult<Option<Value>> {
+        let function = match self.functions.get(name) {
+            Some(function) => Rc::clone(function),
+            None => return Err(self.state.error(format!("I don't know how to {name}"))),
+        };
+
+        let args = match args {
+            Node::Body(args) => args.as_slice(),
+            _ => &[],
+        };
+        // every argument is evaluated before any computed parameter name,
+        // which is the order the VM runs them in
+        let mut values = vec![];
+        for arg in args {
+            values.push(self.eval(arg)?);
+        }
+        let mut frame = HashMap::new();
+        for (param, value) in function.params.iter().zip(values) {
+            // "x and :x both name a parameter x, anything else is evaluated to get its name
+            let name = match param {
+                Node::Literal(name) | Node::Variable(name, _) => name.to_string(),
+                param => {
+                    let value = self.eval(param)?;
+                    self.state.to_name(value)?
+                }
+            };
+            frame.insert(name, Some(value));
+        }
+
+        self.state.check_depth()?;
+        self.state.frames.push(frame);
+        // calls are nested on the Rust stack, so deep recursion carries on in
+        // segments on the heap rather than overflowing before the depth limit
+        let res = stacker::maybe_grow(STACK_RED_ZONE, STACK_SEGMENT_SIZE, || {
+            self.exec_block(&function.body)
+        });
+        self.state.frames.pop();
+
+        match res? {
+            Flow::Output(value) => Ok(Some(value)),
+            _ => Ok(None),
+        }
+    }
+
+    fn eval_number(&mut self, node: &Node) -> RuntimeResult<f64> {
+        let value = self.eval(node)?;
+        self.state.to_number(value)
+    }
+
+    fn eval_bool(&mut self, node: &Node) -> RuntimeResult<bool> {
+        let value = self.eval(node)?;
+        self.state.to_bool(value)
+    }
+}
+
+/// The error given when OUTPUT is run outside of a defined function
+pub(crate) const OUTPUT_OUTSIDE_FUNCTION: &str = "Can only use OUTPUT inside a function";
+
+///
+/// Returns whether a FOR loop has gone past its end
+///
+pub(crate) fn for_finished(value: f64, end: f64, step: f64) -> bool {
+    (step > 0.0 && value > end) || (step < 0.0 && value < end)
+}
+
+impl<'a> State<'a> {
+    pub(crate) fn new(output: &'a mut dyn OutputChannel) -> Self {
+        State {
+            output,
+            scoping: ScopingModel::default(),
+            turtle: Turtle::default(),
+            drawing: Drawing::default(),
+            globals: HashMap::new(),
+            frames: vec![],
+            span: None,
+            seed: 0x2545_f491_4f6c_dd1d,
+            limits: Limits::default(),
+            steps: 0,
+            segments: 0,
+            started: Instant::now(),
+        }
+    }
+
+    ///
+    /// Resets the counts checked against the limits at the start of a run
+    ///
+    pub(crate) fn start(&mut self) {
+        self.steps = 0;
+        self.segments = 0;
+        self.started = Instant::now();
+    }
+
+    ///
+    /// Counts a step, failing once the step limit or the timeout is reached
+    ///
+    pub(crate) fn step(&mut self) -> RuntimeResult<()> {
+        self.steps += 1;
+        if let Some(max) = self.limits.max_steps {
+            if self.steps > max {
+                let message = format!("Step limit reached after running {max} steps");
+                return Err(self.limit_error(ErrorKind::StepLimit, message));
+            }
+        }
+        if let Some(timeout) = self.limits.timeout {
+            // reading the clock is slow compared to a step
+            if self.steps.is_multiple_of(TIMEOUT_CHECK_INTERVAL) && self.started.elapsed() > timeout
+            {
+                let message = format!("Timed out after {} seconds", timeout.as_secs_f64());
+                return Err(self.limit_error(ErrorKind::Timeout, message));
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// Checks another function call can be made without going over the depth limit
+    ///
+    pub(crate) fn check_depth(&self) -> RuntimeResult<()> {
+        match self.limits.max_depth {
+            Some(max) if self.frames.len() >= max => {
+                let message = format!("Depth limit reached with {max} nested function calls");
+                Err(self.limit_error(ErrorKind::DepthLimit, message))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn set_seed(&mut self, seed: u64) {
+        // xorshift gets stuck on a seed of 0
+        self.seed = seed.max(1);
+    }
+
+    pub(crate) fn error(&self, message: String) -> RuntimeError {
+        self.limit_error(ErrorKind::Program, message)
+    }
+
+    fn limit_error(&self, kind: ErrorKind, message: String) -> RuntimeError {
+        RuntimeError {
+            message,
+            span: self.span,
+            kind,
+        }
+    }
+
+    pub(crate) fn no_output(&self, keyword: &str) -> RuntimeError {
+        self.error(format!("{keyword} does not output a value"))
+    }
+
+    ///
+    /// The error given when a value is worked out but nothing uses it
+    ///
+    pub(crate) fn unused(&self, value: Value) -> RuntimeError {
+        self.error(format!("Don't know what to do with {value}"))
+    }
+
+    ///
+    /// Returns the value of a node that outputs a value without
+    /// taking any inputs, e.g. XCOR, or None for any other node
+    ///
+    pub(crate) fn query(&self, node: &Node) -> Option<Value> {
+        match node {
+            Node::Xcor => Some(Value::number(round_query(self.turtle.x))),
+            Node::Ycor => Some(Value::number(round_query(self.turtle.y))),
+            Node::Heading => Some(Value::number(round_query(self.turtle.heading))),
+            Node::Pos => Some(Value::List(vec![
+                Value::number(round_query(self.turtle.x)),
+                Value::number(round_query(self.turtle.y)),
+            ])),
+            Node::Pi => Some(Value::number(std::f64::consts::PI)),
+            Node::Color => {
+                let colour = self.turtle.colour;
+                Some(match colour.palette_index() {
+                    Some(index) => Value::number(index as f64),
+                    None => Value::List(vec![
+                        Value::number(colour.r as f64),
+                        Value::number(colour.g as f64),
+                        Value::number(colour.b as f64),
+                    ]),
+                })
+            }
+            Node::Pensize => Some(Value::number(self.turtle.pen_size)),
+            Node::Pendownp => Some(Value::boolean(self.turtle.pen_down)),
+            _ => None,
+        }
+    }
+
+    ///
+    /// Runs a command that takes no inputs, e.g. PENUP, returning
+    /// false if the node is not one of these commands
+    ///
+    pub(crate) fn exec_leaf(&mut self, node: &Node) -> RuntimeResult<bool> {
+        match node {
+            Node::Penup => self.turtle.pen_down = false,
+            Node::Pendown => self.turtle.pen_down = true,
+            Node::Penpaint => self.set_pen_mode(PenMode::Paint),
+            Node::Penerase => self.set_pen_mode(PenMode::Erase),
+            Node::Penreverse => self.set_pen_mode(PenMode::Reverse),
+            Node::Home => {
+                self.move_to(0.0, 0.0)?;
+                self.turtle.heading = 0.0;
+            }
+            Node::Clean => self.drawing.ops.push(DrawOp::Clear),
+            Node::Clearscreen => {
+                self.drawing.ops.push(DrawOp::Clear);
+                self.turtle.x = 0.0;
+                self.turtle.y = 0.0;
+                self.turtle.heading = 0.0;
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    ///
+    /// Runs a unary command with the value of its input
+    /// OUTPUT is not handled here as it returns from the function
+    ///
+    pub(crate) fn command(&mut self, op: &UnaryOp, value: Value) -> RuntimeResult<()> {
+        match op {
+            UnaryOp::Forward => {
+                let distance = self.to_number(value)?;
+                self.move_by(distance, 0.0)?;
+            }
+            UnaryOp::Back => {
+                let distance = self.to_number(value)?;
+                self.move_by(-distance, 0.0)?;
+            }
+            UnaryOp::Left => {
+                let distance = self.to_number(value)?;
+                self.move_by(distance, -90.0)?;
+            }
+            UnaryOp::Right => {
+                let distance = self.to_number(value)?;
+                self.move_by(distance, 90.0)?;
+            }
+            UnaryOp::Turn => {
+                let degrees = self.to_number(value)?;
+                self.turtle.heading = normalise_heading(self.turtle.heading + degrees);
+            }
+            UnaryOp::Setheading => {
+                let degrees = self.to_number(value)?;
+                self.turtle.heading = normalise_heading(degrees);
+            }
+            UnaryOp::Setx => {
+                let x = self.to_number(value)?;
+                self.move_to(x, self.turtle.y)?;
+            }
+            UnaryOp::Sety => {
+                let y = self.to_number(value)?;
+                self.move_to(self.turtle.x, y)?;
+            }
+            UnaryOp::Setpos => {
+                let (x, y) = self.to_point(value)?;
+                self.move_to(x, y)?;
+            }
+            UnaryOp::Circle => {
+                let radius = self.to_number(value)?;
+                self.draw_arc(360.0, radius)?;
+            }
+            UnaryOp::Setpencolor => self.turtle.colour = self.to_colour(value)?,
+            UnaryOp::Setbackground => self.drawing.background = self.to_colour(value)?,
+            UnaryOp::Setpensize => {
+                let size = self.to_number(value)?;
+                if size <= 0.0 {
+                    return Err(self.error(format!("SETPENSIZE doesn't like {size} as input")));
+                }
+                self.turtle.pen_size = size;
+            }
+            UnaryOp::Print => {
+                self.output.write(&value.to_print_string());
+                self.output.end_line();
+            }
+            UnaryOp::Show => {
+                self.output.write(&value.to_string());
+                self.output.end_line();
+            }
+            UnaryOp::Type => self.output.write(&value.to_print_string()),
+            UnaryOp::Local => {
+                let names = match value {
+                    Value::Word(name) => vec![name],
+                    Value::List(items) => items.iter().map(Value::to_string).collect(),
+                };
+                for name in names {
+                    self.declare_local(name, None)?;
+                }
+            }
+            _ => {
+                let value = self.unary(op, value)?;
+                return Err(self.unused(value));
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// Runs a binary command with the values of its inputs
+    /// IF, WHILE and TO are not handled here as they run code
+    ///
+    pub(crate) fn binary_command(
+        &mut self,
+        op: &BinaryOp,
+        a: Value,
+        b: Value,
+    ) -> RuntimeResult<()> {
+        match op {
+            BinaryOp::Make => {
+                let name = self.to_name(a)?;
+                self.assign(name, b);
+            }
+            BinaryOp::Addassign => {
+                let name = self.to_name(a)?;
+                let current = match self.lookup(&name, Scope::Unresolved)?.as_number() {
+                    Some(n) => n,
+                    None => return Err(self.error(format!("{name} is not a number"))),
+                };
+                let amount = self.to_number(b)?;
+                let value = self.check_number(current + amount)?;
+                self.assign(name, value);
+            }
+            BinaryOp::Localmake => {
+                let name = self.to_name(a)?;
+                self.declare_local(name, Some(b))?;
+            }
+            BinaryOp::Setxy => {
+                let x = self.to_number(a)?;
+                let y = self.to_number(b)?;
+                self.move_to(x, y)?;
+            }
+            BinaryOp::Arc => {
+                let angle = self.to_number(a)?;
+                let radius = self.to_number(b)?;
+                self.draw_arc(angle, radius)?;
+            }
+            _ => {
+                let value = self.binary(op, a, b)?;
+                return Err(self.unused(value));
+            }
+        }
+        Ok(())
+    }
+
+    ///
+    /// Works out the value of a unary operation from the value of its input
+    ///
+    pub(crate) fn unary(&mut self, op: &UnaryOp, value: Value) -> RuntimeResult<Value> {
+        match op {
+            UnaryOp::Not => Ok(Value::boolean(!self.to_bool(value)?)),
+            UnaryOp::Sqrt => {
+                let n = self.to_number(value)?;
+                if n < 0.0 {
+                    return Err(self.error(format!("SQRT doesn't like {n} as input")));
+                }
+                self.check_number(n.sqrt())
+            }
+            UnaryOp::Abs => self.math(value, f64::abs),
+            UnaryOp::Int => self.math(value, f64::trunc),
+            UnaryOp::Round => self.math(value, f64::round),
+            UnaryOp::Sin => self.math(value, |n| n.to_radians().sin()),
+            UnaryOp::Cos => self.math(value, |n| n.to_radians().cos()),
+            UnaryOp::Tan => self.math(value, |n| n.to_radians().tan()),
+            UnaryOp::Arctan => self.math(value, |n| n.atan().to_degrees()),
+            UnaryOp::Random => {
+                let n = self.to_number(value)?;
+                if n < 1.0 || n.fract() != 0.0 {
+                    return Err(self.error(format!("RANDOM doesn't like {n} as input")));
+                }
+                let random = self.next_random() % (n as u64);
+                Ok(Value::number(random as f64))
+            }
+            UnaryOp::First | UnaryOp::Last | UnaryOp::Butfirst | UnaryOp::Butlast => {
+                let items = items_of(&value);
+                if items.is_empty() {
+                    return Err(
+                        self.error(format!("{} doesn't like {value} as input", op.keyword()))
+                    );
+                }
+                let last = items.len() - 1;
+                Ok(match op {
+                    UnaryOp::First => items[0].clone(),
+                    UnaryOp::Last => items[last].clone(),
+                    UnaryOp::Butfirst => rebuild(&value, items[1..].to_vec()),
+                    _ => rebuild(&value, items[..last].to_vec()),
+                })
+            }
+            UnaryOp::Count => Ok(Value::number(items_of(&value).len() as f64)),
+            UnaryOp::Emptyp => Ok(Value::boolean(items_of(&value).is_empty())),
+            UnaryOp::Uppercase => {
+                let word = self.to_word(value, op.keyword())?;
+                Ok(Value::Word(word.to_uppercase()))
+            }
+            UnaryOp::Lowercase => {
+                let word = self.to_word(value, op.keyword())?;
+                Ok(Value::Word(word.to_lowercase()))
+            }
+            UnaryOp::Numberp => Ok(Value::boolean(value.as_number().is_some())),
+            UnaryOp::Wordp => Ok(Value::boolean(matches!(value, Value::Word(_)))),
+            UnaryOp::Char => {
+                let n = self.to_number(value)?;
+                match char::from_u32(n as u32).filter(|_| n >= 0.0 && n.fract() == 0.0) {
+                    Some(c) => Ok(Value::Word(c.to_string())),
+                    None => Err(self.error(format!("CHAR doesn't like {n} as input"))),
+                }
+            }
+            UnaryOp::Ascii => {
+                let word = self.to_word(value, op.keyword())?;
+                match word.chars().next() {
+                    Some(c) => Ok(Value::number(c as u32 as f64)),
+                    None => Err(self.error(String::from("ASCII doesn't like an empty word"))),
+                }
+            }
+            UnaryOp::Towards => {
+                let (x, y) = self.to_point(value)?;
+                let (dx, dy) = (x - self.turtle.x, y - self.turtle.y);
+                let heading = normalise_heading(dx.atan2(dy).to_degrees());
+                Ok(Value::number(round_query(heading)))
+            }
+            UnaryOp::Distance => {
+                let (x, y) = self.to_point(value)?;
+                let distance = (x - self.turtle.x).hypot(y - self.turtle.y);
+                Ok(Value::number(round_query(distance)))
+            }
+            _ => Err(self.no_output(op.keyword())),
+        }
+    }
+
+    ///
+    /// Works out the value of a binary operation from the values of its inputs
+    ///
+    pub(crate) fn binary(&mut self, op: &BinaryOp, a: Value, b: Value) -> RuntimeResult<Value> {
+        match op {
+            BinaryOp::Add => self.arithmetic(a, b, |a, b| a + b),
+            BinaryOp::Sub => self.arithmetic(a, b, |a, b| a - b),
+            BinaryOp::Mul => self.arithmetic(a, b, |a, b| a * b),
+            BinaryOp::Power => self.arithmetic(a, b, f64::powf),
+            BinaryOp::Div | BinaryOp::Modulo | BinaryOp::Remainder => {
+                let a = self.to_number(a)?;
+                let b = self.to_number(b)?;
+                if b == 0.0 {
+                    return Err(self.error(String::from("Can't divide by zero")));
+                }
+                self.check_number(match op {
+                    BinaryOp::Div => a / b,
+                    BinaryOp::Modulo => ((a % b) + b) % b,
+                    _ => a % b,
+                })
+            }
+            BinaryOp::Eq | BinaryOp::Ne => {
+                Ok(Value::boolean(a.equals(&b) == matches!(op, BinaryOp::Eq)))
+            }
+            BinaryOp::Gt | BinaryOp::Lt | BinaryOp::Ge | BinaryOp::Le => {
+                let a = self.to_number(a)?;
+                let b = self.to_number(b)?;
+                Ok(Value::boolean(match op {
+                    BinaryOp::Gt => a > b,
+                    BinaryOp::Lt => a < b,
+                    BinaryOp::Ge => a >= b,
+                    _ => a <= b,
+                }))
+            }
+            BinaryOp::And | BinaryOp::Or | BinaryOp::Xor => {
+                let a = self.to_bool(a)?;
+                let b = self.to_bool(b)?;
+                Ok(Value::boolean(match op {
+                    BinaryOp::And => a && b,
+                    BinaryOp::Or => a || b,
+                    _ => a != b,
+                }))
+            }
+            BinaryOp::Item => {
+                let index = self.to_number(a)?;
+                let items = items_of(&b);
+                if index < 1.0 || index.fract() != 0.0 || index as usize > items.len() {
+                    return Err(self.error(format!("ITEM doesn't like {index} as input")));
+                }
+                Ok(items[index as usize - 1].clone())
+            }
+            BinaryOp::Fput | BinaryOp::Lput => match (&a, b) {
+                (_, Value::List(mut items)) => {
+                    match op {
+                        BinaryOp::Fput => items.insert(0, a),
+                        _ => items.push(a),
+                    }
+                    Ok(Value::List(items))
+                }
+                (Value::Word(a), Value::Word(b)) => Ok(Value::Word(match op {
+                    BinaryOp::Fput => format!("{a}{b}"),
+                    _ => format!("{b}{a}"),
+                })),
+                (_, value) => {
+                    Err(self.error(format!("{} doesn't like {value} as input", op.keyword())))
+                }
+            },
+            BinaryOp::List => Ok(Value::List(vec![a, b])),
+            BinaryOp::Sentence => {
+                let mut items = vec![];
+                for value in [a, b] {
+                    match value {
+                        Value::List(values) => items.extend(values),
+                        word => items.push(word),
+                    }
+                }
+                Ok(Value::List(items))
+            }
+            BinaryOp::Word => {
+                let a = self.to_word(a, op.keyword())?;
+                let b = self.to_word(b, op.keyword())?;
+                Ok(Value::Word(a + &b))
+            }
+            BinaryOp::Memberp => Ok(Value::boolean(
+                items_of(&b).iter().any(|other| a.equals(other)),
+            )),
+            _ => Err(self.no_output(op.keyword())),
+        }
+    }
+
+    ///
+    /// Returns the step of a FOR loop, which defaults to counting
+    /// towards the end one at a time
+    ///
+    pub(crate) fn for_step(&self, start: f64, end: f64, step: Option<f64>) -> RuntimeResult<f64> {
+        let step = match step {
+            Some(step) => step,
+            None if end < start => -1.0,
+            None => 1.0,
+        };
+        if step == 0.0 {
+            return Err(self.error(String::from("FOR step cannot be zero")));
+        }
+        Ok(step)
+    }
+
+    ///
+    /// Sets the variable of a FOR loop in the current function call,
+    /// or as a global variable outside of functions
+    ///
+    pub(crate) fn set_for_var(&mut self, var: &str, value: f64) {
+        match self.frames.last_mut() {
+            Some(frame) => {
+                frame.insert(var.to_string(), Some(Value::number(value)));
+            }
+            None => {
+                self.globals.insert(var.to_string(), Value::number(value));
+            }
+        }
+    }
+
+    ///
+    /// Returns the value of a variable, where unresolved variables
+    /// are looked up according to the scoping model
+    ///
+    pub(crate) fn lookup(&self, name: &str, scope: Scope) -> RuntimeResult<Value> {
+        let scope = match (scope, self.scoping) {
+            (Scope::Unresolved, ScopingModel::Dynamic) => Scope::Dynamic,
+            (Scope::Unresolved, ScopingModel::Lexical) => match self.frames.last() {
+                Some(frame) if frame.contains_key(name) => Scope::Local,
+                _ => Scope::Global,
+            },
+            (scope, _) => scope,
+        };
+        let value = match scope {
+            Scope::Local => self
+                .frames
+                .last()
+                .and_then(|frame| frame.get(name))
+                .cloned(),
+            Scope::Dynamic => match self.frames.iter().rev().find_map(|frame| frame.get(name)) {
+                Some(value) => Some(value.clone()),
+                None => self.globals.get(name).cloned().map(Some),
+            },
+            _ => self.globals.get(name).cloned().map(Some),
+        };
+        match value {
+            Some(Some(value)) => Ok(value),
+            _ => Err(self.error(format!("{name} has no value"))),
+        }
+    }
+
+    ///
+    /// Sets the value of a variable for MAKE and ADDASSIGN
+    /// Dynamic scoping changes the innermost function call that has the
+    /// variable, lexical scoping only checks the current function call,
+    /// and otherwise the global variable is set
+    ///
+    fn assign(&mut self, name: String, value: Value) {
+        let frame = match self.scoping {
+            ScopingModel::Dynamic => self
+                .frames
+                .iter_mut()
+                .rev()
+                .find(|frame| frame.contains_key(&name)),
+            ScopingModel::Lexical => self
+                .frames
+                .last_mut()
+                .filter(|frame| frame.contains_key(&name)),
+        };
+        match frame {
+            Some(frame) => {
+                frame.insert(name, Some(value));
+            }
+            None => {
+                self.globals.insert(name, value);
+            }
+        }
+    }
+
+    fn declare_local(&mut self, name: String, value: Option<Value>) -> RuntimeResult<()> {
+        match self.frames.last_mut() {
+            Some(frame) => {
+                frame.insert(name, value);
+                Ok(())
+            }
+            None => Err(self.error(String::from("Can only use LOCAL inside a function"))),
+        }
+    }
+
+    fn set_pen_mode(&mut self, mode: PenMode) {
+        self.turtle.pen_mode = mode;
+        self.turtle.pen_down = true;
+    }
+
+    ///
+    /// Moves the turtle to the given position, drawing a line if the pen is down
+    ///
+    fn move_to(&mut self, x: f64, y: f64) -> RuntimeResult<()> {
+        if self.turtle.pen_down {
+            self.draw_line((self.turtle.x, self.turtle.y), (x, y))?;
+        }
+        self.turtle.x = x;
+        self.turtle.y = y;
+        Ok(())
+    }
+
+    ///
+    /// Moves the turtle by the given distance in the direction of its
+    /// heading turned clockwise by the given number of degrees
+    ///
+    fn move_by(&mut self, distance: f64, turn: f64) -> RuntimeResult<()> {
+        let direction = (self.turtle.heading + turn).to_radians();
+        let x = self.turtle.x + distance * direction.sin();
+        let y = self.turtle.y + distance * direction.cos();
+        self.move_to(x, y)
+    }
+
+    ///
+    /// Draws an arc of a circle centred on the turtle without moving it,
+    /// starting at the heading of the turtle and going clockwise
+    ///
+    fn draw_arc(&mut self, angle: f64, radius: f64) -> RuntimeResult<()> {
+        if !self.turtle.pen_down || angle == 0.0 {
+            return Ok(());
+        }
+        let (cx, cy) = (self.turtle.x, self.turtle.y);
+        let point = |degrees: f64| {
+            let radians = degrees.to_radians();
+            (cx + radius * radians.sin(), cy + radius * radians.cos())
+        };
+        let segments = (angle.abs() / ARC_STEP_DEGREES).ceil().max(1.0) as usize;
+        let mut from = point(self.turtle.heading);
+        for i in 1..=segments {
+            // a long arc is drawn as a great many segments, so each one
+            // counts as a step to keep it within the step limit and timeout
+            self.step()?;
+            let to = point(self.turtle.heading + angle * i as f64 / segments as f64);
+            self.draw_line(from, to)?;
+            from = to;
+        }
+        Ok(())
+    }
+
+    fn draw_line(&mut self, from: (f64, f64), to: (f64, f64)) -> RuntimeResult<()> {
+        self.segments += 1;
+        if let Some(max) = self.limits.max_segments {
+            if self.segments > max {
+                let message = format!("Segment limit reached after drawing {max} segments");
+                return Err(self.limit_error(ErrorKind::SegmentLimit, message));
+            }
+        }
+        self.drawing.ops.push(DrawOp::Line(Line {
+            from,
+            to,
+            colour: self.turtle.colour,
+            width: self.turtle.pen_size,
+            mode: self.turtle.pen_mode,
+        }));
+        Ok(())
+    }
+
+    ///
+    /// xorshift64* so that RANDOM gives the same numbers for the same seed
+    ///
+    fn next_random(&mut self) -> u64 {
+        self.seed ^= self.seed >> 12;
+        self.seed ^= self.seed << 25;
+        self.seed ^= self.seed >> 27;
+        self.seed.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11
+    }
+
+    fn check_number(&self, n: f64) -> RuntimeResult<Value> {
+        if n.is_finite() {
+            Ok(Value::number(n))
+        } else {
+            Err(self.error(String::from("Number is out of range")))
+        }
+    }
+
+    fn math(&self, value: Value, f: impl Fn(f64) -> f64) -> RuntimeResult<Value> {
+        let n = self.to_number(value)?;
+        self.check_number(f(n))
+    }
+
+    fn arithmetic(&self, a: Value, b: Value, f: impl Fn(f64, f64) -> f64) -> RuntimeResult<Value> {
+        let a = self.to_number(a)?;
+        let b = self.to_number(b)?;
+        self.check_number(f(a, b))
+    }
+
+    pub(crate) fn to_number(&self, value: Value) -> RuntimeResult<f64> {
+        match value.as_number() {
+            Some(n) => Ok(n),
+            None => Err(self.error(format!("{value} is not a number"))),
+        }
+    }
+
+    pub(crate) fn to_bool(&self, value: Value) -> RuntimeResult<bool> {
+        match value.as_bool() {
+            Some(b) => Ok(b),
+            None => Err(self.error(format!("{value} is not TRUE or FALSE"))),
+        }
+    }
+
+    fn to_word(&self, value: Value, keyword: &str) -> RuntimeResult<String> {
+        match value {
+            Value::Word(word) => Ok(word),
+            value => Err(self.error(format!("{keyword} doesn't like {value} as input"))),
+        }
+    }
+
+    ///
+    /// Checks the value is the name of a variable, e.g. the lhs of MAKE
+    ///
+    pub(crate) fn to_name(&self, value: Value) -> RuntimeResult<String> {
+        match value {
+            Value::Word(name) => Ok(name),
+            value => Err(self.error(format!("{value} is not a variable name"))),
+        }
+    }
+
+    fn to_point(&self, value: Value) -> RuntimeResult<(f64, f64)> {
+        if let Value::List(items) = &value {
+            if let [x, y] = items.as_slice() {
+                if let (Some(x), Some(y)) = (x.as_number(), y.as_number()) {
+                    return Ok((x, y));
+                }
+            }
+        }
+        Err(self.error(format!("{value} is not a [x y] position")))
+    }
+
+    fn to_colour(&self, value: Value) -> RuntimeResult<Colour> {
+        let colour = match &value {
+            Value::Word(word) => Colour::from_word(word),
+            Value::List(items) => {
+                let components: Option<Vec<f64>> = items.iter().map(Value::as_number).collect();
+                components.and_then(|c| Colour::from_components(&c))
+            }
+        };
+        match colour {
+            Some(colour) => Ok(colour),
+            None => Err(self.error(format!("{value} is not a colour"))),
+        }
+    }
+}
+
+///
+/// Returns the value of a data list, whose items are never evaluated
+///
+pub(crate) fn data_value(node: &Node) -> Value {
+    match node {
+        Node::List(items) => Value::List(items.iter().map(data_value).collect()),
+        Node::Literal(word) => Value::Word(word.to_string()),
+        _ => Value::List(vec![]),
+    }
+}
+
+///
+/// Returns the items of a list, or the characters of a word
+///
+fn items_of(value: &Value) -> Vec<Value> {
+    match value {
+        Value::List(items) => items.clone(),
+        Value::Word(word) => word.chars().map(|c| Value::Word(c.to_string())).collect(),
+    }
+}
+
+///
+/// Puts items back into the same kind of value they were taken from
+///
+fn rebuild(value: &Value, items: Vec<Value>) -> Value {
+    match value {
+        Value::List(_) => Value::List(items),
+        Value::Word(_) => Value::Word(items.iter().map(Value::to_string).collect()),
+    }
+}
+
+fn normalise_heading(degrees: f64) -> f64 {
+    degrees.rem_euclid(360.0)
+}
+
+///
+/// Rounds positions given to the program so that turning and moving back
+/// again compares equal to where the turtle started, e.g. EQ XCOR "200
+///
+fn round_query(n: f64) -> f64 {
+    (n * 1e9).round() / 1e9
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenise_source;
+    use crate::output::CapturedOutput;
+    use crate::parser::parse_program;
+    use crate::resolver::resolve;
+
+    type RunResult = Result<(Vec<String>, Drawing, Turtle), String>;
+
+    fn run_with(source: &str, model: ScopingModel) -> RunResult {
+        let mut ast = parse_program(tokenise_source(source).unwrap()).unwrap();
+        resolve(&mut ast, model).unwrap();
+        let mut output = CapturedOutput::new();
+        let mut interpreter = Interpreter::new(&mut output).with_scoping(model);
+        let res = interpreter.run(&ast).map_err(|e| e.to_string());
+        let turtle = interpreter.turtle().clone();
+        let drawing = interpreter.into_drawing();
+        res.map(|()| (output.into_lines(), drawing, turtle))
+    }
+
+    fn run(source: &str) -> RunResult {
+        run_with(source, ScopingModel::Dynamic)
+    }
+
+    fn printed(source: &str) -> Vec<String> {
+        run(source).unwrap().0
+    }
+
+    ///
+    /// Returns the ends of the lines drawn, rounded so that
+    /// the error left by sin and cos does not matter
+    ///
+    fn ends(drawing: &Drawing) -> Vec<[f64; 4]> {
+        drawing
+            .visible_lines()
+            .map(|line| [line.from.0, line.from.1, line.to.0, line.to.1].map(round_query))
+            .collect()
+    }
+
+    #[test]
+    fn moves_the_turtle() {
+        let source =
+            "PENDOWN\nFORWARD \"10\nPENUP\nBACK \"4\nPENDOWN\nTURN \"90\nFORWARD \"5\nRIGHT \"2\n";
+        let (_, drawing, turtle) = run(source).unwrap();
+        assert_eq!(
+            drawing.ops[0],
+            DrawOp::Line(Line {
+                from: (0.0, 0.0),
+                to: (0.0, 10.0),
+                colour: PALETTE[7].1,
+                width: 1.0,
+                mode: PenMode::Paint,
+            })
+        );
+        // RIGHT moves sideways, which is down when facing right
+        assert_eq!(
+            ends(&drawing),
+            [
+                [0.0, 0.0, 0.0, 10.0],
+                [0.0, 6.0, 5.0, 6.0],
+                [5.0, 6.0, 5.0, 4.0]
+            ]
+        );
+        assert_eq!((round_query(turtle.x), round_query(turtle.y)), (5.0, 4.0));
+        assert_eq!(turtle.heading, 90.0);
+        assert!(turtle.pen_down);
+    }
+
+    #[test]
+    fn assigns_variables() {
+        let source = "MAKE \"x \"1\nADDASSIGN \"x \"2\nPRINT :x\nMAKE \"x \"word\nPRINT :x\n";
+        assert_eq!(printed(source), ["3", "word"]);
+        assert_eq!(
+            run("MAKE \"x \"1\nADDASSIGN \"x \"a\n").unwrap_err(),
+            "2:1: a is not a number"
+        );
+    }
+
+    #[test]
+    fn runs_conditionals_and_loops() {
+        let source = "MAKE \"i \"0\nWHILE LT :i \"3 [\n    ADDASSIGN \"i \"1\n    \
+                      IF EQ :i \"2 [\n        PRINT :i\n    ]\n]\nPRINT :i\n";
+        assert_eq!(printed(source), ["2", "3"]);
+
+        // the body of a loop whose condition is false is never run
+        let source =
+            "WHILE EQ \"1 \"2 [\n    PRINT \"never\n]\nIF EQ \"1 \"2 [\n    PRINT \"never\n]\n";
+        assert!(printed(source).is_empty());
+    }
+
+    #[test]
+    fn calls_procedures() {
+        let source = "TO square :n\n    PENDOWN\n    FORWARD :n\n    TURN \"90\n    FORWARD :n\n    \
+                      TURN \"90\n    FORWARD :n\n    TURN \"90\n    FORWARD :n\n    TURN \"90\nEND\n\
+                      square \"10\n";
+        let (_, drawing, turtle) = run(source).unwrap();
+        assert_eq!(
+            ends(&drawing),
+            [
+                [0.0, 0.0, 0.0, 10.0],
+                [0.0, 10.0, 10.0, 10.0],
+                [10.0, 10.0, 10.0, 0.0],
+                [10.0, 0.0, 0.0, 0.0]
+            ]
+        );
+        assert_eq!(turtle.heading, 0.0);
+    }
+
+    #[test]
+    fn outputs_and_stops() {
+        let source = "TO f :n\n    IF LT :n \"0 [\n        STOP\n    ]\n    OUTPUT * :n \"2\n    \
+                      PRINT \"never\nEND\nPRINT f \"3\nf \"-1\nPRINT f \"-1\n";
+        assert_eq!(run(source).unwrap_err(), "10:1: f did not output a value");
+        let source = source.replace("PRINT f \"-1\n", "");
+        assert_eq!(printed(&source), ["6"]);
+    }
+
+    #[test]
+    fn looks_up_variables_dynamically_or_lexically() {
+        let source = "MAKE \"x \"1\nTO show\n    PRINT :x\nEND\n\
+                      TO g :x\n    show\nEND\ng \"2\nshow\n";
+        let dynamic = run_with(source, ScopingModel::Dynamic).unwrap().0;
+        let lexical = run_with(source, ScopingModel::Lexical).unwrap().0;
+        assert_eq!(dynamic, ["2", "1"]);
+        assert_eq!(lexical, ["1", "1"]);
+    }
+
+    #[test]
+    fn errors_give_where_they_happened() {
+        assert_eq!(run("PRINT :y\n").unwrap_err(), "1:1: y has no value");
+        let source = "MAKE \"x \"1\nIF EQ :x \"1 [\n    FORWARD / :x \"0\n]\n";
+        assert_eq!(run(source).unwrap_err(), "3:5: Can't divide by zero");
+        let source = "TO f :n\n    FORWARD :n\nEND\nf \"abc\n";
+        assert_eq!(run(source).unwrap_err(), "2:5: abc is not a number");
+    }
+}
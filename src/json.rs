@@ -0,0 +1,206 @@
+use crate::ast::{Node, Scope};
+use crate::lexer::tokenise_source;
+use crate::parser::parse_program;
+use crate::printer::print_logo;
+use crate::visit::{walk_nodes_mut, VisitorMut};
+use serde::Serialize;
+
+///
+/// Version of the JSON schema, which changes whenever a change to the AST
+/// changes the JSON it is written as
+///
+pub const SCHEMA_VERSION: u64 = 1;
+
+///
+/// Struct representing a JSON document, which stores the version of the
+/// schema next to the statements of the program
+///
+/// Each Node is written as an object with the name of its variant as the
+/// only key, e.g. {"Literal": "10"}, apart from variants without fields
+/// which are written as a string, e.g. "Penup". Operations and scopes are
+/// written the same way, and a Span is written as {"line": 1, "column": 1}
+///
+#[derive(Serialize)]
+struct Document<'a> {
+    version: u64,
+    ast: &'a [Node],
+}
+
+///
+/// This function is responsible for writing an AST as a JSON document
+///
+pub fn to_json(ast: &[Node]) -> Result<String, String> {
+    let document = Document {
+        version: SCHEMA_VERSION,
+        ast,
+    };
+    serde_json::to_string_pretty(&document).map_err(|e| format!("Could not write JSON: {e}"))
+}
+
+///
+/// This function is responsible for reading an AST back from a JSON document
+/// The version is checked before anything else, so a document written with
+/// a different schema is rejected with an error naming both versions
+///
+/// The AST is checked the same way as code, by printing it as logo code and
+/// parsing that back, so it is rejected unless the parser would give the
+/// same AST. The scopes of its variables are cleared to be resolved again
+///
+pub fn from_json(json: &str) -> Result<Vec<Node>, String> {
+    let value: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| format!("Invalid JSON: {e}"))?;
+
+    match value.get("version").and_then(|version| version.as_u64()) {
+        Some(SCHEMA_VERSION) => {}
+        Some(version) => {
+            return Err(format!(
+                "Unsupported JSON schema version {version}, expected {SCHEMA_VERSION}"
+            ))
+        }
+        None => return Err(String::from("JSON document has no schema version")),
+    }
+
+    let ast = value.get("ast").cloned().unwrap_or_default();
+    let mut ast: Vec<Node> =
+        serde_json::from_value(ast).map_err(|e| format!("Invalid AST in JSON: {e}"))?;
+    walk_nodes_mut(&mut ClearScopes, &mut ast);
+
+    let parsed = tokenise_source(&print_logo(&ast))
+        .and_then(parse_program)
+        .map_err(|e| format!("Invalid AST in JSON: {e}"))?;
+    let same = parsed.len() == ast.len()
+        && parsed
+            .iter()
+            .zip(&ast)
+            .all(|(parsed, node)| parsed.eq_ignoring_spans(node));
+    if !same {
+        return Err(String::from(
+            "Invalid AST in JSON: it is not the AST of the code it is written as",
+        ));
+    }
+    Ok(ast)
+}
+
+///
+/// Struct representing a visitor that sets every variable back to Unresolved
+///
+struct ClearScopes;
+
+impl VisitorMut for ClearScopes {
+    fn visit_variable_mut(&mut self, _name: &mut String, scope: &mut Scope) {
+        *scope = Scope::Unresolved;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolver::{resolve, ScopingModel};
+    use std::fs;
+    use std::path::Path;
+
+    fn parse(source: &str) -> Vec<Node> {
+        parse_program(tokenise_source(source).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn examples_round_trip() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+        let mut count = 0;
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|extension| extension == "lg") {
+                let ast = parse(&fs::read_to_string(&path).unwrap());
+                assert_eq!(from_json(&to_json(&ast).unwrap()).unwrap(), ast);
+
+                // the scopes of a resolved AST are resolved again after reading it
+                let mut resolved = ast.clone();
+                resolve(&mut resolved, ScopingModel::Lexical).unwrap();
+                assert_eq!(from_json(&to_json(&resolved).unwrap()).unwrap(), ast);
+                count += 1;
+            }
+        }
+        assert!(count > 0);
+    }
+
+    ///
+    /// If this fails because the AST changed how it is written as JSON,
+    /// update the snapshot and bump SCHEMA_VERSION
+    ///
+    #[test]
+    fn writes_fixed_snapshot() {
+        let source = "TO f :a\n    OUTPUT + :a \"1\nEND\nFOR [i \"1 \"2] [\n    PENUP\n]\nSHOW LIST [1 f] f \"2\n";
+        let expected = r#"{"version": 1, "ast": [
+            {"Statement": {"span": {"line": 1, "column": 1}, "node": {"BinaryExpr": {
+                "op": {"Func": "f"},
+                "lhs": {"Body": [{"Variable": ["a", "Unresolved"]}]},
+                "rhs": {"Body": [{"Statement": {"span": {"line": 2, "column": 5}, "node": {"UnaryExpr": {
+                    "op": "Output",
+                    "child": {"BinaryExpr": {"op": "Add", "lhs": {"Variable": ["a", "Unresolved"]}, "rhs": {"Literal": "1"}}}
+                }}}}]}
+            }}}},
+            {"Statement": {"span": {"line": 4, "column": 1}, "node": {"For": {
+                "var": "i",
+                "start": {"Literal": "1"},
+                "end": {"Literal": "2"},
+                "step": null,
+                "body": {"Body": [{"Statement": {"span": {"line": 5, "column": 5}, "node": "Penup"}}]}
+            }}}},
+            {"Statement": {"span": {"line": 7, "column": 1}, "node": {"UnaryExpr": {
+                "op": "Show",
+                "child": {"BinaryExpr": {
+                    "op": "List",
+                    "lhs": {"List": [{"Literal": "1"}, {"Literal": "f"}]},
+                    "rhs": {"Caller": {"name": "f", "args": {"Body": [{"Literal": "2"}]}}}
+                }}
+            }}}}
+        ]}"#;
+        let written: serde_json::Value =
+            serde_json::from_str(&to_json(&parse(source)).unwrap()).unwrap();
+        let expected: serde_json::Value = serde_json::from_str(expected).unwrap();
+        assert_eq!(written, expected);
+        assert_eq!(SCHEMA_VERSION, 1);
+    }
+
+    #[test]
+    fn rejects_other_versions() {
+        assert_eq!(
+            from_json(r#"{"version": 2, "ast": []}"#),
+            Err(String::from(
+                "Unsupported JSON schema version 2, expected 1"
+            ))
+        );
+        assert_eq!(
+            from_json(r#"{"ast": []}"#),
+            Err(String::from("JSON document has no schema version"))
+        );
+    }
+
+    #[test]
+    fn rejects_asts_the_parser_would_not_make() {
+        // OUTPUT outside of a function
+        let json = r#"{"version": 1, "ast": [{"UnaryExpr": {"op": "Output", "child": {"Literal": "1"}}}]}"#;
+        assert_eq!(
+            from_json(json),
+            Err(String::from(
+                "Invalid AST in JSON: OUTPUT can only be used inside a function"
+            ))
+        );
+
+        // a command that is not in a statement
+        let json = r#"{"version": 1, "ast": [{"UnaryExpr": {"op": "Forward", "child": {"Literal": "1"}}}]}"#;
+        assert_eq!(
+            from_json(json),
+            Err(String::from(
+                "Invalid AST in JSON: it is not the AST of the code it is written as"
+            ))
+        );
+
+        // a call to a function that is not defined
+        let json = r#"{"version": 1, "ast": [{"Statement": {"span": {"line": 1, "column": 1}, "node": {"Caller": {"name": "f", "args": {"Body": []}}}}}]}"#;
+        assert_eq!(
+            from_json(json),
+            Err(String::from("Invalid AST in JSON: wrong format"))
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use crate::ast::Span;
 use std::{
     fs::File,
     io::{self, BufRead, BufReader},
@@ -6,47 +7,104 @@ use std::{
 
 type LexerResult<T> = Result<T, String>;
 
+///
+/// Struct representing a single token along with the
+/// position in the logo code that it was read from
+///
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub value: String,
+    pub span: Span,
+}
+
+impl Token {
+    pub fn as_str(&self) -> &str {
+        self.value.as_str()
+    }
+}
+
 ///
 /// This function is responsible for tokenising the logo code
 /// For each line, it will split by whitespace
 /// Then it considers any words with prefix of a literal (") or
 /// variable (:), and creates a whitespace between the prefix and
-/// the name and stores it in the output Vec<String>
-/// A newline String is also added at the end of each line to help
+/// the name and stores it in the output Vec<Token>
+/// Brackets written against a word (e.g. `[i` or `"10]`) are split
+/// into their own tokens
+/// A newline Token is also added at the end of each line to help
 /// validating code when parsing
-/// 
-pub fn tokenise_logo<P>(filename: P) -> LexerResult<Vec<String>>
+///
+pub fn tokenise_logo<P>(filename: P) -> LexerResult<Vec<Token>>
 where
     P: AsRef<Path>,
 {
+    if let Ok(lines) = lines_from_file(filename) {
+        tokenise_lines(lines.iter().map(String::as_str))
+    } else {
+        Err(String::from("File does not exist"))
+    }
+}
+
+///
+/// Tokenises logo code that is already in memory
+/// This behaves the same as tokenise_logo
+///
+pub fn tokenise_source(source: &str) -> LexerResult<Vec<Token>> {
+    tokenise_lines(source.lines())
+}
+
+///
+/// Tokenises each of the given lines, where the span of every token
+/// records its line and column (both counting from 1)
+///
+fn tokenise_lines<'a>(lines: impl Iterator<Item = &'a str>) -> LexerResult<Vec<Token>> {
     let mut tokens = Vec::new();
 
-    if let Ok(lines) = lines_from_file(filename) {
-        for line in lines {
-            let split_line: Vec<&str> = line.split_whitespace().collect();
-            for word in split_line {
-                if word.starts_with('\"') {
-                    tokens.push(String::from("\""));
-                    let res = remove_prefix(word);
-                    match res {
-                        Some(remainder) => tokens.push(remainder.to_string()),
-                        None => return Err(String::from("Could not remove prefix")),
-                    }
-                } else if word.starts_with(':') {
-                    tokens.push(String::from(":"));
-                    let res = remove_prefix(word);
-                    match res {
-                        Some(remainder) => tokens.push(remainder.to_string()),
-                        None => return Err(String::from("Could not remove prefix")),
-                    }
-                } else {
-                    tokens.push(word.to_string());
+    for (index, line) in lines.enumerate() {
+        let line_number = index + 1;
+        let mut push = |value: &str, offset: usize| {
+            tokens.push(Token {
+                value: value.to_string(),
+                span: Span {
+                    line: line_number,
+                    column: line[..offset].chars().count() + 1,
+                },
+            })
+        };
+
+        for word in line.split_whitespace() {
+            // byte offset of the word in the line
+            let mut offset = word.as_ptr() as usize - line.as_ptr() as usize;
+
+            // brackets may be written against a word, e.g. [i "0 "10]
+            let mut word = word;
+            while word.len() > 1 && word.starts_with('[') {
+                push("[", offset);
+                word = &word[1..];
+                offset += 1;
+            }
+            let mut closing = 0;
+            while word.len() > 1 && word.ends_with(']') {
+                closing += 1;
+                word = &word[..word.len() - 1];
+            }
+
+            if word.starts_with('\"') || word.starts_with(':') {
+                push(&word[..1], offset);
+                let res = remove_prefix(word);
+                match res {
+                    Some(remainder) => push(remainder, offset + 1),
+                    None => return Err(String::from("Could not remove prefix")),
                 }
+            } else {
+                push(word, offset);
+            }
+
+            for i in 0..closing {
+                push("]", offset + word.len() + i);
             }
-            tokens.push(String::from("\n"));
         }
-    } else {
-        return Err(String::from("File does not exist"));
+        push("\n", line.len());
     }
 
     Ok(tokens)
@@ -54,15 +112,14 @@ where
 
 ///
 /// Returns a vector of lines in the given file
-/// 
+///
 fn lines_from_file(filename: impl AsRef<Path>) -> io::Result<Vec<String>> {
     BufReader::new(File::open(filename)?).lines().collect()
 }
 
 ///
 /// Removes the specified prefix and returns the remaining String
-/// 
+///
 fn remove_prefix(s: &str) -> Option<&str> {
     s.chars().next().map(|c| &s[c.len_utf8()..])
 }
-
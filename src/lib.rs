@@ -0,0 +1,22 @@
+pub mod ast;
+pub mod builder;
+pub mod bytecode;
+pub mod canvas;
+pub mod colour;
+pub mod compiler;
+pub mod diff;
+pub mod dot;
+pub mod formatter;
+pub mod interpreter;
+pub mod json;
+pub mod lexer;
+pub mod output;
+pub mod parser;
+pub mod preview;
+pub mod printer;
+pub mod raster;
+pub mod resolver;
+pub mod sexp;
+pub mod svg;
+pub mod visit;
+pub mod vm;
@@ -1,19 +1,219 @@
-use clap::Parser;
-use std::collections::HashMap;
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{panic, thread};
 
-mod ast;
-mod lexer;
-mod parser;
+use ast_builder::ast::*;
+use ast_builder::bytecode::*;
+use ast_builder::canvas::*;
+use ast_builder::compiler::*;
+use ast_builder::diff::*;
+use ast_builder::dot::*;
+use ast_builder::formatter::*;
+use ast_builder::interpreter::*;
+use ast_builder::json::*;
+use ast_builder::lexer::*;
+use ast_builder::output::*;
+use ast_builder::parser::*;
+use ast_builder::preview::*;
+use ast_builder::printer::*;
+use ast_builder::raster::*;
+use ast_builder::resolver::*;
+use ast_builder::sexp::*;
+use ast_builder::svg::*;
+use ast_builder::vm::*;
 
-use crate::ast::*;
-use crate::lexer::*;
-use crate::parser::*;
+/// Size of the stack programs are run with, which is enough for the
+/// default depth limit even in debug builds, where the interpreter runs
+/// deeper function calls on stack segments of its own
+const PROGRAM_STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// Largest depth limit that can be given, since each nested call
+/// takes up memory until the program stops
+const MAX_DEPTH_LIMIT: usize = 100_000;
 
 /// A simple program to parse a logo code file using clap.
 #[derive(Parser)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+#[command(group = ArgGroup::new("running").args(["run", "preview"]))]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Path to a file
-    file_path: std::path::PathBuf,
+    #[arg(required = true)]
+    file_path: Option<PathBuf>,
+
+    /// Resolve variables lexically instead of dynamically
+    #[arg(long)]
+    lexical_scope: bool,
+
+    /// Run the program and print the drawing operations instead of the AST
+    #[arg(long)]
+    run: bool,
+
+    /// Run the program and draw what the turtle draws in the terminal
+    #[arg(long)]
+    preview: bool,
+
+    /// Compile the program to bytecode and run it with the VM
+    #[arg(long, requires = "running")]
+    vm: bool,
+
+    /// Format the AST is printed in
+    #[arg(long, value_enum, default_value_t = AstFormat::Debug, conflicts_with = "running")]
+    format: AstFormat,
+
+    #[command(flatten)]
+    limits: LimitArgs,
+}
+
+///
+/// Enum representing the formats the AST can be printed in
+///
+#[derive(Clone, Copy, ValueEnum)]
+enum AstFormat {
+    /// Rust debug output, written to stderr
+    Debug,
+    /// Versioned JSON document, written to stdout
+    Json,
+    /// Logo code printed back from the AST, written to stdout
+    Logo,
+    /// S-expressions, written to stdout
+    Sexp,
+    /// Graphviz graph of the tree, written to stdout
+    Dot,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run a logo code file and write what the turtle draws to an image
+    Render(RenderArgs),
+    /// Compile a logo code file and print its bytecode
+    Disasm(DisasmArgs),
+    /// Format logo code files in place
+    Fmt(FmtArgs),
+    /// List the statements that changed between two versions of a program
+    Diff(DiffArgs),
+}
+
+#[derive(clap::Args)]
+struct RenderArgs {
+    /// Path to a file
+    file_path: PathBuf,
+
+    /// Path of the image to write, which is a PNG file if it ends
+    /// in .png and an SVG file otherwise
+    #[arg(short, long)]
+    output: PathBuf,
+
+    /// Width of the canvas in pixels
+    #[arg(long, default_value_t = 500)]
+    width: u32,
+
+    /// Height of the canvas in pixels
+    #[arg(long, default_value_t = 500)]
+    height: u32,
+
+    /// Canvas position of the turtle's origin, defaults to the centre
+    #[arg(long, num_args = 2, value_names = ["X", "Y"], allow_negative_numbers = true)]
+    origin: Option<Vec<f64>>,
+
+    /// Number of pixels per turtle step
+    #[arg(long, default_value_t = 1.0)]
+    scale: f64,
+
+    /// Resolve variables lexically instead of dynamically
+    #[arg(long)]
+    lexical_scope: bool,
+
+    /// Compile the program to bytecode and run it with the VM
+    #[arg(long)]
+    vm: bool,
+
+    #[command(flatten)]
+    limits: LimitArgs,
+}
+
+///
+/// Limits that stop programs that never finish
+///
+#[derive(clap::Args)]
+struct LimitArgs {
+    /// Most steps the program can run, where a step is a node for the
+    /// interpreter and an instruction for the VM, and each segment of an arc
+    #[arg(long)]
+    max_steps: Option<u64>,
+
+    /// Most function calls that can be running at once, up to 100000
+    #[arg(long, default_value_t = DEFAULT_MAX_DEPTH)]
+    max_depth: usize,
+
+    /// Most line segments the program can draw
+    #[arg(long)]
+    max_segments: Option<usize>,
+
+    /// Most seconds the program can run for
+    #[arg(long)]
+    timeout: Option<f64>,
+}
+
+impl LimitArgs {
+    fn limits(&self) -> Result<Limits, String> {
+        let timeout = match self.timeout {
+            Some(seconds) => match Duration::try_from_secs_f64(seconds) {
+                Ok(timeout) => Some(timeout),
+                Err(_) => return Err(format!("Invalid timeout of {seconds} seconds")),
+            },
+            None => None,
+        };
+        if self.max_depth > MAX_DEPTH_LIMIT {
+            return Err(format!(
+                "Invalid depth limit of {}, which can be at most {MAX_DEPTH_LIMIT}",
+                self.max_depth
+            ));
+        }
+        Ok(Limits {
+            max_steps: self.max_steps,
+            max_depth: Some(self.max_depth),
+            max_segments: self.max_segments,
+            timeout,
+        })
+    }
+}
+
+#[derive(clap::Args)]
+struct DisasmArgs {
+    /// Path to a file
+    file_path: PathBuf,
+
+    /// Resolve variables lexically instead of dynamically
+    #[arg(long)]
+    lexical_scope: bool,
+}
+
+#[derive(clap::Args)]
+struct FmtArgs {
+    /// Paths to the files to format
+    #[arg(required = true)]
+    file_paths: Vec<PathBuf>,
+
+    /// Only check whether the files are formatted, without changing them
+    #[arg(long)]
+    check: bool,
+}
+
+#[derive(clap::Args)]
+struct DiffArgs {
+    /// Path to the old version of the program
+    old_path: PathBuf,
+
+    /// Path to the new version of the program
+    new_path: PathBuf,
+
+    /// Resolve variables lexically instead of dynamically
+    #[arg(long)]
+    lexical_scope: bool,
 }
 
 ///
@@ -21,44 +221,278 @@ struct Args {
 /// - Processes command line args
 /// - Tokenise the logo code
 /// - Parse the tokens into an AST
-/// - Print debug the parsed ast to stdout
+/// - Resolve the scope of every variable in the AST
+/// - Print debug the parsed ast to stdout, or run it when asked to
+/// - For --preview, run the AST and draw the drawing in the terminal
+/// - For the render command, run the AST and write the drawing to a file
+/// - For the disasm command, compile the AST and print its bytecode
+/// - For the fmt command, print the AST of each file back into it
+/// - For the diff command, list the statements that differ between two files
 /// 
 fn main() -> Result<(), ()> {
     let args: Args = Args::parse();
 
-    // Access the parsed arguments
-    let file_path = args.file_path;
+    // the interpreter makes function calls on the Rust stack,
+    // so programs are run on a thread with a large one
+    let res = thread::Builder::new()
+        .stack_size(PROGRAM_STACK_SIZE)
+        .spawn(move || run_command(args))
+        .map_err(|e| format!("Could not start the program: {e}"))
+        .and_then(|handle| handle.join().unwrap_or_else(|e| panic::resume_unwind(e)));
 
-    // Tokenise the code
-    if let Ok(tokens) = tokenise_logo(file_path) {
-        let mut token_iterator = tokens.into_iter().peekable();
-        let mut brackets = 0;   // this validates the start and end of each codeblock
+    if let Err(e) = res {
+        eprintln!("{e}");
+        return Err(());
+    }
 
-        // Function map is used to keep track of any defined functions where the key is
-        // the name of the function and value points to the AST root node of that function
-        let mut function_map: HashMap<String, Node> = HashMap::new();
+    Ok(())
+}
 
-        // Parse the tokens
-        match parse_logo(&mut token_iterator, &mut brackets, &mut function_map) {
-            Ok(ast) => {
-                if brackets != 0 {
-                    eprintln!("Invalid codeblock");
-                    return Err(());
+///
+/// Run the command given by the command line args
+///
+fn run_command(args: Args) -> Result<(), String> {
+    match args.command {
+        Some(Command::Render(render_args)) => render(render_args),
+        Some(Command::Disasm(disasm_args)) => disasm(disasm_args),
+        Some(Command::Fmt(fmt_args)) => fmt(fmt_args),
+        Some(Command::Diff(diff_args)) => diff_programs(diff_args),
+        None => {
+            // Access the parsed arguments
+            let file_path = args.file_path.unwrap_or_default();
+            let model = scoping_model(args.lexical_scope);
+
+            match load_program(&file_path, model) {
+                Ok(ast) => {
+                    if args.preview {
+                        match run_program(&ast, model, args.vm, args.limits.limits()?) {
+                            // draw the lines in the terminal
+                            Ok(drawing) => {
+                                let (columns, rows) = preview_size();
+                                print!("{}", render_preview(&drawing, columns, rows));
+                                Ok(())
+                            }
+                            Err(e) => Err(e),
+                        }
+                    } else if args.run {
+                        match run_program(&ast, model, args.vm, args.limits.limits()?) {
+                            // dbg print drawing operations
+                            Ok(drawing) => {
+                                dbg!(drawing);
+                                Ok(())
+                            }
+                            Err(e) => Err(e),
+                        }
+                    } else {
+                        match args.format {
+                            // dbg print parsed ast
+                            AstFormat::Debug => {
+                                dbg!(ast);
+                            }
+                            AstFormat::Json => println!("{}", to_json(&ast)?),
+                            AstFormat::Logo => print!("{}", print_logo(&ast)),
+                            AstFormat::Sexp => print!("{}", to_sexp(&ast)),
+                            AstFormat::Dot => print!("{}", to_dot(&ast)),
+                        }
+                        Ok(())
+                    }
                 }
-                
-                // dbg print parsed ast
-                dbg!(ast);
-                
-            }
-            Err(e) => {
-                eprintln!("{e}");
-                return Err(());
+                Err(e) => Err(e),
             }
         }
+    }
+}
+
+///
+/// Run the program and write the drawing to the output file
+///
+fn render(args: RenderArgs) -> Result<(), String> {
+    let model = scoping_model(args.lexical_scope);
+    let ast = load_program(&args.file_path, model)?;
+    let drawing = run_program(&ast, model, args.vm, args.limits.limits()?)?;
+
+    let options = CanvasOptions {
+        width: args.width,
+        height: args.height,
+        origin: args.origin.map(|origin| (origin[0], origin[1])),
+        scale: args.scale,
+    };
+    // the format of the image is picked by the extension of the output file
+    let is_png = args
+        .output
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("png"));
+    let contents = if is_png {
+        rasterise(&drawing, &options).to_png()?
     } else {
-        eprintln!("Error with tokenising");
-        return Err(());
+        render_svg(&drawing, &options).into_bytes()
+    };
+    std::fs::write(&args.output, contents)
+        .map_err(|e| format!("Could not write {}: {e}", args.output.display()))
+}
+
+///
+/// Number of columns and rows the preview can use, leaving a row
+/// for the prompt. Defaults to 80x24 when not run in a terminal
+///
+fn preview_size() -> (usize, usize) {
+    match terminal_size::terminal_size() {
+        Some((terminal_size::Width(width), terminal_size::Height(height))) => {
+            (width as usize, (height as usize).saturating_sub(1).max(1))
+        }
+        None => (80, 23),
     }
+}
 
+///
+/// Compile the program and print the disassembled bytecode
+///
+fn disasm(args: DisasmArgs) -> Result<(), String> {
+    let model = scoping_model(args.lexical_scope);
+    let ast = load_program(&args.file_path, model)?;
+    let program = compile(&ast)?;
+    print!("{}", disassemble(&program));
     Ok(())
 }
+
+///
+/// Format each file in place, or with --check list the files that are not
+/// formatted and fail if there are any
+///
+fn fmt(args: FmtArgs) -> Result<(), String> {
+    let mut unformatted = 0;
+    for file_path in &args.file_paths {
+        let source = std::fs::read_to_string(file_path)
+            .map_err(|e| format!("Could not read {}: {e}", file_path.display()))?;
+        let formatted =
+            format_logo(&source).map_err(|e| format!("{}: {e}", file_path.display()))?;
+        if formatted == source {
+            continue;
+        }
+
+        if args.check {
+            println!("{} is not formatted", file_path.display());
+            unformatted += 1;
+        } else {
+            std::fs::write(file_path, formatted)
+                .map_err(|e| format!("Could not write {}: {e}", file_path.display()))?;
+        }
+    }
+
+    match unformatted {
+        0 => Ok(()),
+        1 => Err(String::from("1 file is not formatted")),
+        n => Err(format!("{n} files are not formatted")),
+    }
+}
+
+///
+/// Print each statement that was removed, inserted or changed between
+/// the two programs with the line it is on, followed by its code
+///
+fn diff_programs(args: DiffArgs) -> Result<(), String> {
+    let model = scoping_model(args.lexical_scope);
+    let old = load_program(&args.old_path, model)?;
+    let new = load_program(&args.new_path, model)?;
+
+    for change in diff(&old, &new) {
+        match change {
+            Change::Removed { node, .. } => {
+                println!("removed line {}:", line_of(&node));
+                print_statement("-", &node);
+            }
+            Change::Inserted { node, .. } => {
+                println!("inserted line {}:", line_of(&node));
+                print_statement("+", &node);
+            }
+            Change::Changed { old, new, .. } => {
+                println!(
+                    "changed line {} (now line {}):",
+                    line_of(&old),
+                    line_of(&new)
+                );
+                print_statement("-", &old);
+                print_statement("+", &new);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn line_of(node: &Node) -> usize {
+    match node {
+        Node::Statement { span, .. } => span.line,
+        _ => 0,
+    }
+}
+
+fn print_statement(prefix: &str, node: &Node) {
+    for line in print_logo(std::slice::from_ref(node)).lines() {
+        println!("{prefix} {line}");
+    }
+}
+
+fn scoping_model(lexical_scope: bool) -> ScopingModel {
+    if lexical_scope {
+        ScopingModel::Lexical
+    } else {
+        ScopingModel::Dynamic
+    }
+}
+
+///
+/// Tokenise the logo code in the given file, parse it and resolve its variables
+/// Files ending in .json are read as an AST written by --format json instead
+///
+fn load_program(file_path: &Path, model: ScopingModel) -> Result<Vec<Node>, String> {
+    let is_json = file_path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"));
+    if is_json {
+        let json = std::fs::read_to_string(file_path)
+            .map_err(|e| format!("Could not read {}: {e}", file_path.display()))?;
+        let mut ast = from_json(&json)?;
+        resolve(&mut ast, model)?;
+        return Ok(ast);
+    }
+
+    // Tokenise the code
+    if let Ok(tokens) = tokenise_logo(file_path) {
+        // Parse the tokens
+        let mut ast = parse_program(tokens)?;
+        resolve(&mut ast, model)?;
+        Ok(ast)
+    } else {
+        Err(String::from("Error with tokenising"))
+    }
+}
+
+///
+/// Run the program, streaming anything it prints to stdout
+/// The program is compiled and run with the VM when vm is set,
+/// otherwise the AST is run with the interpreter
+///
+fn run_program(
+    ast: &[Node],
+    model: ScopingModel,
+    vm: bool,
+    limits: Limits,
+) -> Result<Drawing, String> {
+    let mut output = StdoutChannel;
+    if vm {
+        let program = compile(ast)?;
+        let mut vm = Vm::new(&mut output).with_scoping(model).with_limits(limits);
+        return match vm.run(&program) {
+            Ok(()) => Ok(vm.into_drawing()),
+            Err(e) => Err(e.to_string()),
+        };
+    }
+
+    let mut interpreter = Interpreter::new(&mut output)
+        .with_scoping(model)
+        .with_limits(limits);
+    match interpreter.run(ast) {
+        Ok(()) => Ok(interpreter.into_drawing()),
+        Err(e) => Err(e.to_string()),
+    }
+}
@@ -0,0 +1,120 @@
+use std::io::{self, Write};
+
+///
+/// This trait is responsible for receiving the text written by the
+/// PRINT, SHOW and TYPE commands when a program is evaluated
+/// PRINT and SHOW call write followed by end_line whereas TYPE only
+/// calls write, so its text stays on the current line
+///
+pub trait OutputChannel {
+    fn write(&mut self, text: &str);
+    fn end_line(&mut self);
+}
+
+///
+/// Output channel that streams all text straight to stdout
+///
+#[derive(Debug, Default)]
+pub struct StdoutChannel;
+
+impl OutputChannel for StdoutChannel {
+    fn write(&mut self, text: &str) {
+        print!("{text}");
+        // TYPE does not end the line so flush to show partial lines
+        let _ = io::stdout().flush();
+    }
+
+    fn end_line(&mut self) {
+        println!();
+    }
+}
+
+///
+/// Output channel that collects all text into lines so that it
+/// can be inspected afterwards, e.g. in tests
+///
+#[derive(Debug, Default)]
+pub struct CapturedOutput {
+    lines: Vec<String>,
+    current: Option<String>,
+}
+
+impl CapturedOutput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// Returns the captured lines, including a final line
+    /// that was started by TYPE but never ended
+    ///
+    pub fn into_lines(mut self) -> Vec<String> {
+        if let Some(line) = self.current.take() {
+            self.lines.push(line);
+        }
+        self.lines
+    }
+}
+
+impl OutputChannel for CapturedOutput {
+    fn write(&mut self, text: &str) {
+        self.current.get_or_insert_with(String::new).push_str(text);
+    }
+
+    fn end_line(&mut self) {
+        let line = self.current.take().unwrap_or_default();
+        self.lines.push(line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::Interpreter;
+    use crate::lexer::tokenise_source;
+    use crate::parser::parse_program;
+
+    ///
+    /// Output channel that keeps the text exactly as it would be
+    /// streamed, writing a newline for each end of line
+    ///
+    #[derive(Default)]
+    struct Streamed(String);
+
+    impl OutputChannel for Streamed {
+        fn write(&mut self, text: &str) {
+            self.0.push_str(text);
+        }
+
+        fn end_line(&mut self) {
+            self.0.push('\n');
+        }
+    }
+
+    fn run(source: &str, output: &mut dyn OutputChannel) {
+        let ast = parse_program(tokenise_source(source).unwrap()).unwrap();
+        Interpreter::new(output).run(&ast).unwrap();
+    }
+
+    #[test]
+    fn writes_print_show_and_type() {
+        let source = "PRINT [1 [2 3]]\nSHOW [1 [2 3]]\nPRINT \"a\nSHOW \"a\n\
+                      PRINT []\nSHOW []\nTYPE \"a\nTYPE [1 2]\nPRINT \"b\nTYPE \"end\n";
+        let mut output = Streamed::default();
+        run(source, &mut output);
+        assert_eq!(output.0, "1 [2 3]\n[1 [2 3]]\na\na\n\n[]\na1 2b\nend");
+    }
+
+    #[test]
+    fn captures_lines() {
+        let mut output = CapturedOutput::new();
+        run("TYPE \"a\nPRINT \"b\nPRINT \"c\nTYPE \"d\n", &mut output);
+        assert_eq!(output.into_lines(), ["ab", "c", "d"]);
+
+        let mut output = CapturedOutput::new();
+        output.end_line();
+        output.write("x");
+        output.end_line();
+        assert_eq!(output.into_lines(), ["", "x"]);
+    }
+}
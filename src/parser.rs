@@ -1,8 +1,113 @@
 use crate::ast::*;
+use crate::colour::Colour;
+use crate::lexer::Token;
+use crate::visit::{walk_nodes, walk_unary, Visitor};
 use std::{collections::HashMap, iter::Peekable};
 
 type ParseResult<T> = Result<T, String>;
 
+///
+/// Every word that parse_command reads as a keyword rather than as the
+/// name of a defined function, along with END and ELSE
+///
+pub const KEYWORDS: &[&str] = &[
+    "PENUP",
+    "PENDOWN",
+    "PENPAINT",
+    "PENERASE",
+    "PENREVERSE",
+    "PENSIZE",
+    "PENDOWNP",
+    "HEADING",
+    "XCOR",
+    "YCOR",
+    "POS",
+    "HOME",
+    "CLEARSCREEN",
+    "CS",
+    "CLEAN",
+    "PI",
+    "COLOR",
+    "STOP",
+    "FORWARD",
+    "BACK",
+    "LEFT",
+    "RIGHT",
+    "SETPENCOLOR",
+    "SETPENSIZE",
+    "SETBACKGROUND",
+    "SETBG",
+    "TURN",
+    "SETHEADING",
+    "SETX",
+    "SETY",
+    "SETXY",
+    "SETPOS",
+    "ARC",
+    "CIRCLE",
+    "TOWARDS",
+    "DISTANCE",
+    "PRINT",
+    "SHOW",
+    "TYPE",
+    "OUTPUT",
+    "MAKE",
+    "ADDASSIGN",
+    "LOCAL",
+    "LOCALMAKE",
+    "IF",
+    "WHILE",
+    "IFELSE",
+    "FOR",
+    "EQ",
+    "NE",
+    "GT",
+    "LT",
+    "GE",
+    "LE",
+    "AND",
+    "OR",
+    "XOR",
+    "NOT",
+    "SQRT",
+    "ABS",
+    "INT",
+    "ROUND",
+    "SIN",
+    "COS",
+    "TAN",
+    "ARCTAN",
+    "RANDOM",
+    "FIRST",
+    "LAST",
+    "BUTFIRST",
+    "BF",
+    "BUTLAST",
+    "BL",
+    "COUNT",
+    "EMPTYP",
+    "ITEM",
+    "FPUT",
+    "LPUT",
+    "LIST",
+    "SENTENCE",
+    "SE",
+    "WORD",
+    "MEMBERP",
+    "UPPERCASE",
+    "LOWERCASE",
+    "NUMBERP",
+    "WORDP",
+    "CHAR",
+    "ASCII",
+    "POWER",
+    "MODULO",
+    "REMAINDER",
+    "TO",
+    "END",
+    "ELSE",
+];
+
 /// 
 /// This trait is responsible for handing shared / similar behaviour
 /// between different expressions when parsing
@@ -10,7 +115,7 @@ type ParseResult<T> = Result<T, String>;
 trait ParseProcedure {
     fn do_parse_procedure(
         &self,
-        token_iterator: &mut Peekable<std::vec::IntoIter<String>>,
+        token_iterator: &mut Peekable<std::vec::IntoIter<Token>>,
         brackets: &mut i32,
         function_map: &mut HashMap<String, Node>,
     ) -> ParseResult<Node>;
@@ -23,7 +128,7 @@ trait ParseProcedure {
 /// 
 fn parse_procedure<T: ParseProcedure>(
     procedure: T,
-    token_iterator: &mut Peekable<std::vec::IntoIter<String>>,
+    token_iterator: &mut Peekable<std::vec::IntoIter<Token>>,
     brackets: &mut i32,
     function_map: &mut HashMap<String, Node>,
 ) -> ParseResult<Node> {
@@ -37,7 +142,7 @@ impl ParseProcedure for BinaryOp {
     /// 
     fn do_parse_procedure(
         &self,
-        token_iterator: &mut Peekable<std::vec::IntoIter<String>>,
+        token_iterator: &mut Peekable<std::vec::IntoIter<Token>>,
         brackets: &mut i32,
         function_map: &mut HashMap<String, Node>,
     ) -> ParseResult<Node> {
@@ -46,6 +151,15 @@ impl ParseProcedure for BinaryOp {
             BinaryOp::Sub => parse_binary(BinaryOp::Sub, token_iterator, brackets, function_map),
             BinaryOp::Mul => parse_binary(BinaryOp::Mul, token_iterator, brackets, function_map),
             BinaryOp::Div => parse_binary(BinaryOp::Div, token_iterator, brackets, function_map),
+            BinaryOp::Power => {
+                parse_binary(BinaryOp::Power, token_iterator, brackets, function_map)
+            }
+            BinaryOp::Modulo => {
+                parse_binary(BinaryOp::Modulo, token_iterator, brackets, function_map)
+            }
+            BinaryOp::Remainder => {
+                parse_binary(BinaryOp::Remainder, token_iterator, brackets, function_map)
+            }
             BinaryOp::Make => parse_binary(BinaryOp::Make, token_iterator, brackets, function_map),
             BinaryOp::Addassign => {
                 parse_binary(BinaryOp::Addassign, token_iterator, brackets, function_map)
@@ -54,8 +168,29 @@ impl ParseProcedure for BinaryOp {
             BinaryOp::Ne => parse_binary(BinaryOp::Ne, token_iterator, brackets, function_map),
             BinaryOp::Gt => parse_binary(BinaryOp::Gt, token_iterator, brackets, function_map),
             BinaryOp::Lt => parse_binary(BinaryOp::Lt, token_iterator, brackets, function_map),
+            BinaryOp::Ge => parse_binary(BinaryOp::Ge, token_iterator, brackets, function_map),
+            BinaryOp::Le => parse_binary(BinaryOp::Le, token_iterator, brackets, function_map),
             BinaryOp::And => parse_binary(BinaryOp::And, token_iterator, brackets, function_map),
             BinaryOp::Or => parse_binary(BinaryOp::Or, token_iterator, brackets, function_map),
+            BinaryOp::Xor => parse_binary(BinaryOp::Xor, token_iterator, brackets, function_map),
+            BinaryOp::Item => parse_binary(BinaryOp::Item, token_iterator, brackets, function_map),
+            BinaryOp::Fput => parse_binary(BinaryOp::Fput, token_iterator, brackets, function_map),
+            BinaryOp::Lput => parse_binary(BinaryOp::Lput, token_iterator, brackets, function_map),
+            BinaryOp::List => parse_binary(BinaryOp::List, token_iterator, brackets, function_map),
+            BinaryOp::Sentence => {
+                parse_binary(BinaryOp::Sentence, token_iterator, brackets, function_map)
+            }
+            BinaryOp::Word => parse_binary(BinaryOp::Word, token_iterator, brackets, function_map),
+            BinaryOp::Memberp => {
+                parse_binary(BinaryOp::Memberp, token_iterator, brackets, function_map)
+            }
+            BinaryOp::Localmake => {
+                parse_binary(BinaryOp::Localmake, token_iterator, brackets, function_map)
+            }
+            BinaryOp::Setxy => {
+                parse_binary(BinaryOp::Setxy, token_iterator, brackets, function_map)
+            }
+            BinaryOp::Arc => parse_binary(BinaryOp::Arc, token_iterator, brackets, function_map),
             BinaryOp::If => {
                 parse_conditional_binary(BinaryOp::If, token_iterator, brackets, function_map)
             }
@@ -72,13 +207,31 @@ impl ParseProcedure for BinaryOp {
     }
 }
 
+impl ParseProcedure for TernaryOp {
+    ///
+    /// Consider the different operations of each TernaryOp and process accordingly
+    /// 
+    fn do_parse_procedure(
+        &self,
+        token_iterator: &mut Peekable<std::vec::IntoIter<Token>>,
+        brackets: &mut i32,
+        function_map: &mut HashMap<String, Node>,
+    ) -> ParseResult<Node> {
+        match self {
+            TernaryOp::IfElse => {
+                parse_conditional_ternary(TernaryOp::IfElse, token_iterator, brackets, function_map)
+            }
+        }
+    }
+}
+
 impl ParseProcedure for UnaryOp {
     ///
     /// Consider the different operations of each UnaryOp and process accordingly
     /// 
     fn do_parse_procedure(
         &self,
-        token_iterator: &mut Peekable<std::vec::IntoIter<String>>,
+        token_iterator: &mut Peekable<std::vec::IntoIter<Token>>,
         brackets: &mut i32,
         function_map: &mut HashMap<String, Node>,
     ) -> ParseResult<Node> {
@@ -90,7 +243,7 @@ impl ParseProcedure for UnaryOp {
             UnaryOp::Left => parse_unary(UnaryOp::Left, token_iterator, brackets, function_map),
             UnaryOp::Right => parse_unary(UnaryOp::Right, token_iterator, brackets, function_map),
             UnaryOp::Setpencolor => {
-                parse_unary(UnaryOp::Setpencolor, token_iterator, brackets, function_map)
+                parse_colour(UnaryOp::Setpencolor, token_iterator, brackets, function_map)
             }
             UnaryOp::Turn => parse_unary(UnaryOp::Turn, token_iterator, brackets, function_map),
             UnaryOp::Setheading => {
@@ -98,6 +251,60 @@ impl ParseProcedure for UnaryOp {
             }
             UnaryOp::Setx => parse_unary(UnaryOp::Setx, token_iterator, brackets, function_map),
             UnaryOp::Sety => parse_unary(UnaryOp::Sety, token_iterator, brackets, function_map),
+            UnaryOp::Output => parse_unary(UnaryOp::Output, token_iterator, brackets, function_map),
+            UnaryOp::Not => parse_unary(UnaryOp::Not, token_iterator, brackets, function_map),
+            UnaryOp::Sqrt => parse_unary(UnaryOp::Sqrt, token_iterator, brackets, function_map),
+            UnaryOp::Abs => parse_unary(UnaryOp::Abs, token_iterator, brackets, function_map),
+            UnaryOp::Int => parse_unary(UnaryOp::Int, token_iterator, brackets, function_map),
+            UnaryOp::Round => parse_unary(UnaryOp::Round, token_iterator, brackets, function_map),
+            UnaryOp::Sin => parse_unary(UnaryOp::Sin, token_iterator, brackets, function_map),
+            UnaryOp::Cos => parse_unary(UnaryOp::Cos, token_iterator, brackets, function_map),
+            UnaryOp::Tan => parse_unary(UnaryOp::Tan, token_iterator, brackets, function_map),
+            UnaryOp::Arctan => parse_unary(UnaryOp::Arctan, token_iterator, brackets, function_map),
+            UnaryOp::Random => parse_unary(UnaryOp::Random, token_iterator, brackets, function_map),
+            UnaryOp::First => parse_unary(UnaryOp::First, token_iterator, brackets, function_map),
+            UnaryOp::Last => parse_unary(UnaryOp::Last, token_iterator, brackets, function_map),
+            UnaryOp::Butfirst => {
+                parse_unary(UnaryOp::Butfirst, token_iterator, brackets, function_map)
+            }
+            UnaryOp::Butlast => {
+                parse_unary(UnaryOp::Butlast, token_iterator, brackets, function_map)
+            }
+            UnaryOp::Count => parse_unary(UnaryOp::Count, token_iterator, brackets, function_map),
+            UnaryOp::Emptyp => parse_unary(UnaryOp::Emptyp, token_iterator, brackets, function_map),
+            UnaryOp::Uppercase => {
+                parse_unary(UnaryOp::Uppercase, token_iterator, brackets, function_map)
+            }
+            UnaryOp::Lowercase => {
+                parse_unary(UnaryOp::Lowercase, token_iterator, brackets, function_map)
+            }
+            UnaryOp::Numberp => {
+                parse_unary(UnaryOp::Numberp, token_iterator, brackets, function_map)
+            }
+            UnaryOp::Wordp => parse_unary(UnaryOp::Wordp, token_iterator, brackets, function_map),
+            UnaryOp::Char => parse_unary(UnaryOp::Char, token_iterator, brackets, function_map),
+            UnaryOp::Ascii => parse_unary(UnaryOp::Ascii, token_iterator, brackets, function_map),
+            UnaryOp::Print => parse_unary(UnaryOp::Print, token_iterator, brackets, function_map),
+            UnaryOp::Show => parse_unary(UnaryOp::Show, token_iterator, brackets, function_map),
+            UnaryOp::Type => parse_unary(UnaryOp::Type, token_iterator, brackets, function_map),
+            UnaryOp::Local => parse_unary(UnaryOp::Local, token_iterator, brackets, function_map),
+            UnaryOp::Setpos => parse_unary(UnaryOp::Setpos, token_iterator, brackets, function_map),
+            UnaryOp::Circle => parse_unary(UnaryOp::Circle, token_iterator, brackets, function_map),
+            UnaryOp::Towards => {
+                parse_unary(UnaryOp::Towards, token_iterator, brackets, function_map)
+            }
+            UnaryOp::Distance => {
+                parse_unary(UnaryOp::Distance, token_iterator, brackets, function_map)
+            }
+            UnaryOp::Setpensize => {
+                parse_unary(UnaryOp::Setpensize, token_iterator, brackets, function_map)
+            }
+            UnaryOp::Setbackground => parse_colour(
+                UnaryOp::Setbackground,
+                token_iterator,
+                brackets,
+                function_map,
+            ),
         }
     }
 }
@@ -113,19 +320,40 @@ impl ParseProcedure for UnaryOp {
 /// Note that the WHILE command will trigger this recursively such
 /// that it creates its own ast vector which contains its codeblock
 /// 
+/// Note that each AST subtree is wrapped in a Statement node which
+/// records where in the logo code the subtree starts
+/// 
 pub fn parse_logo(
-    token_iterator: &mut Peekable<std::vec::IntoIter<String>>,
+    token_iterator: &mut Peekable<std::vec::IntoIter<Token>>,
     brackets: &mut i32,
     function_map: &mut HashMap<String, Node>,
 ) -> ParseResult<Vec<Node>> {
     let mut ast = vec![];
 
     loop {
+        // comments are skipped here so that each statement
+        // is given the span of its own first token
+        if token_iterator.peek().map(Token::as_str) == Some("//") {
+            for token in token_iterator.by_ref() {
+                if token.as_str() == "\n" {
+                    break;
+                }
+            }
+            continue;
+        }
+
+        let span = match token_iterator.peek() {
+            Some(token) => token.span,
+            None => Span::default(),
+        };
         match parse_command(token_iterator, brackets, function_map) {
             Ok(res) => match res {
                 Node::Empty => break,
                 Node::Newline => continue,
-                _ => ast.push(res),
+                _ => ast.push(Node::Statement {
+                    span,
+                    node: Box::new(res),
+                }),
             },
             Err(e) => return Err(e.to_string()),
         }
@@ -152,7 +380,7 @@ pub fn parse_logo(
 /// before processing the operation / commands itself
 /// 
 fn parse_command(
-    token_iterator: &mut Peekable<std::vec::IntoIter<String>>,
+    token_iterator: &mut Peekable<std::vec::IntoIter<Token>>,
     brackets: &mut i32,
     function_map: &mut HashMap<String, Node>,
 ) -> ParseResult<Node> {
@@ -160,10 +388,21 @@ fn parse_command(
         match token.as_str() {
             "PENUP" => Ok(Node::Penup),
             "PENDOWN" => Ok(Node::Pendown),
+            "PENPAINT" => Ok(Node::Penpaint),
+            "PENERASE" => Ok(Node::Penerase),
+            "PENREVERSE" => Ok(Node::Penreverse),
+            "PENSIZE" => Ok(Node::Pensize),
+            "PENDOWNP" => Ok(Node::Pendownp),
             "HEADING" => Ok(Node::Heading),
             "XCOR" => Ok(Node::Xcor),
             "YCOR" => Ok(Node::Ycor),
+            "POS" => Ok(Node::Pos),
+            "HOME" => Ok(Node::Home),
+            "CLEARSCREEN" | "CS" => Ok(Node::Clearscreen),
+            "CLEAN" => Ok(Node::Clean),
+            "PI" => Ok(Node::Pi),
             "COLOR" => Ok(Node::Color),
+            "STOP" => Ok(Node::Stop),
             "\"" => parse_leaf(token_iterator, Leaf::Literal),
             ":" => parse_leaf(token_iterator, Leaf::Variable),
 
@@ -174,41 +413,115 @@ fn parse_command(
             "SETPENCOLOR" => {
                 parse_procedure(UnaryOp::Setpencolor, token_iterator, brackets, function_map)
             }
+            "SETPENSIZE" => {
+                parse_procedure(UnaryOp::Setpensize, token_iterator, brackets, function_map)
+            }
+            "SETBACKGROUND" | "SETBG" => parse_procedure(
+                UnaryOp::Setbackground,
+                token_iterator,
+                brackets,
+                function_map,
+            ),
             "TURN" => parse_procedure(UnaryOp::Turn, token_iterator, brackets, function_map),
             "SETHEADING" => {
                 parse_procedure(UnaryOp::Setheading, token_iterator, brackets, function_map)
             }
             "SETX" => parse_procedure(UnaryOp::Setx, token_iterator, brackets, function_map),
             "SETY" => parse_procedure(UnaryOp::Sety, token_iterator, brackets, function_map),
+            "SETXY" => parse_procedure(BinaryOp::Setxy, token_iterator, brackets, function_map),
+            "SETPOS" => parse_procedure(UnaryOp::Setpos, token_iterator, brackets, function_map),
+            "ARC" => parse_procedure(BinaryOp::Arc, token_iterator, brackets, function_map),
+            "CIRCLE" => parse_procedure(UnaryOp::Circle, token_iterator, brackets, function_map),
+            "TOWARDS" => parse_procedure(UnaryOp::Towards, token_iterator, brackets, function_map),
+            "DISTANCE" => {
+                parse_procedure(UnaryOp::Distance, token_iterator, brackets, function_map)
+            }
+            "PRINT" => parse_procedure(UnaryOp::Print, token_iterator, brackets, function_map),
+            "SHOW" => parse_procedure(UnaryOp::Show, token_iterator, brackets, function_map),
+            "TYPE" => parse_procedure(UnaryOp::Type, token_iterator, brackets, function_map),
+            "OUTPUT" => parse_procedure(UnaryOp::Output, token_iterator, brackets, function_map),
 
             "MAKE" => parse_procedure(BinaryOp::Make, token_iterator, brackets, function_map),
             "ADDASSIGN" => {
                 parse_procedure(BinaryOp::Addassign, token_iterator, brackets, function_map)
             }
+            "LOCAL" => parse_procedure(UnaryOp::Local, token_iterator, brackets, function_map),
+            "LOCALMAKE" => {
+                parse_procedure(BinaryOp::Localmake, token_iterator, brackets, function_map)
+            }
             "IF" => parse_procedure(BinaryOp::If, token_iterator, brackets, function_map),
             "WHILE" => parse_procedure(BinaryOp::While, token_iterator, brackets, function_map),
+            "IFELSE" => parse_procedure(TernaryOp::IfElse, token_iterator, brackets, function_map),
+            "FOR" => parse_for(token_iterator, brackets, function_map),
             "EQ" => parse_procedure(BinaryOp::Eq, token_iterator, brackets, function_map),
             "NE" => parse_procedure(BinaryOp::Ne, token_iterator, brackets, function_map),
             "GT" => parse_procedure(BinaryOp::Gt, token_iterator, brackets, function_map),
             "LT" => parse_procedure(BinaryOp::Lt, token_iterator, brackets, function_map),
+            "GE" => parse_procedure(BinaryOp::Ge, token_iterator, brackets, function_map),
+            "LE" => parse_procedure(BinaryOp::Le, token_iterator, brackets, function_map),
             "AND" => parse_procedure(BinaryOp::And, token_iterator, brackets, function_map),
             "OR" => parse_procedure(BinaryOp::Or, token_iterator, brackets, function_map),
+            "XOR" => parse_procedure(BinaryOp::Xor, token_iterator, brackets, function_map),
+            "NOT" => parse_procedure(UnaryOp::Not, token_iterator, brackets, function_map),
+            "SQRT" => parse_procedure(UnaryOp::Sqrt, token_iterator, brackets, function_map),
+            "ABS" => parse_procedure(UnaryOp::Abs, token_iterator, brackets, function_map),
+            "INT" => parse_procedure(UnaryOp::Int, token_iterator, brackets, function_map),
+            "ROUND" => parse_procedure(UnaryOp::Round, token_iterator, brackets, function_map),
+            "SIN" => parse_procedure(UnaryOp::Sin, token_iterator, brackets, function_map),
+            "COS" => parse_procedure(UnaryOp::Cos, token_iterator, brackets, function_map),
+            "TAN" => parse_procedure(UnaryOp::Tan, token_iterator, brackets, function_map),
+            "ARCTAN" => parse_procedure(UnaryOp::Arctan, token_iterator, brackets, function_map),
+            "RANDOM" => parse_procedure(UnaryOp::Random, token_iterator, brackets, function_map),
             "+" => parse_procedure(BinaryOp::Add, token_iterator, brackets, function_map),
             "-" => parse_procedure(BinaryOp::Sub, token_iterator, brackets, function_map),
             "*" => parse_procedure(BinaryOp::Mul, token_iterator, brackets, function_map),
             "/" => parse_procedure(BinaryOp::Div, token_iterator, brackets, function_map),
+            "FIRST" => parse_procedure(UnaryOp::First, token_iterator, brackets, function_map),
+            "LAST" => parse_procedure(UnaryOp::Last, token_iterator, brackets, function_map),
+            "BUTFIRST" | "BF" => {
+                parse_procedure(UnaryOp::Butfirst, token_iterator, brackets, function_map)
+            }
+            "BUTLAST" | "BL" => {
+                parse_procedure(UnaryOp::Butlast, token_iterator, brackets, function_map)
+            }
+            "COUNT" => parse_procedure(UnaryOp::Count, token_iterator, brackets, function_map),
+            "EMPTYP" => parse_procedure(UnaryOp::Emptyp, token_iterator, brackets, function_map),
+            "ITEM" => parse_procedure(BinaryOp::Item, token_iterator, brackets, function_map),
+            "FPUT" => parse_procedure(BinaryOp::Fput, token_iterator, brackets, function_map),
+            "LPUT" => parse_procedure(BinaryOp::Lput, token_iterator, brackets, function_map),
+            "LIST" => parse_procedure(BinaryOp::List, token_iterator, brackets, function_map),
+            "SENTENCE" | "SE" => {
+                parse_procedure(BinaryOp::Sentence, token_iterator, brackets, function_map)
+            }
+            "WORD" => parse_procedure(BinaryOp::Word, token_iterator, brackets, function_map),
+            "MEMBERP" => parse_procedure(BinaryOp::Memberp, token_iterator, brackets, function_map),
+            "UPPERCASE" => {
+                parse_procedure(UnaryOp::Uppercase, token_iterator, brackets, function_map)
+            }
+            "LOWERCASE" => {
+                parse_procedure(UnaryOp::Lowercase, token_iterator, brackets, function_map)
+            }
+            "NUMBERP" => parse_procedure(UnaryOp::Numberp, token_iterator, brackets, function_map),
+            "WORDP" => parse_procedure(UnaryOp::Wordp, token_iterator, brackets, function_map),
+            "CHAR" => parse_procedure(UnaryOp::Char, token_iterator, brackets, function_map),
+            "ASCII" => parse_procedure(UnaryOp::Ascii, token_iterator, brackets, function_map),
+            "POWER" => parse_procedure(BinaryOp::Power, token_iterator, brackets, function_map),
+            "MODULO" => parse_procedure(BinaryOp::Modulo, token_iterator, brackets, function_map),
+            "REMAINDER" => {
+                parse_procedure(BinaryOp::Remainder, token_iterator, brackets, function_map)
+            }
 
             "TO" => {
                 *brackets += 1;
                 if let Some(func_name) = token_iterator.next() {
                     match parse_procedure(
-                        BinaryOp::Func(func_name.to_string()),
+                        BinaryOp::Func(func_name.value.to_string()),
                         token_iterator,
                         brackets,
                         function_map,
                     ) {
                         Ok(func_data) => {
-                            function_map.insert(func_name, func_data.clone());
+                            function_map.insert(func_name.value, func_data.clone());
                             Ok(func_data)
                         }
                         Err(e) => Err(e),
@@ -227,7 +540,7 @@ fn parse_command(
             }
             "//" => loop {
                 if let Some(res) = token_iterator.next() {
-                    if res == "\n" {
+                    if res.as_str() == "\n" {
                         return parse_command(token_iterator, brackets, function_map);
                     }
                 }
@@ -246,32 +559,30 @@ fn parse_command(
                 }
             }
             k => {
-                if function_map.contains_key(&k.to_string()) {
-                    let mut args = vec![];
-                    loop {
-                        if let Ok(arg) = parse_command(token_iterator, brackets, function_map) {
-                            match arg {
-                                Node::Newline => break,
-                                _ => {
-                                    args.push(arg);
-                                    if let Some(token) = token_iterator.peek() {
-                                        if token.as_str() == "\n" {
-                                            break;
-                                        }
-                                    }
-                                }
-                            }
-                        } else {
-                            return Err(String::from("cannot parse arg of defined func"));
+                // the number of parameters of a defined function decides how many
+                // args are taken, which also allows calls to be nested in expressions
+                let arity = match function_map.get(k) {
+                    Some(Node::BinaryExpr { lhs, .. }) => match lhs.as_ref() {
+                        Node::Body(params) => params.len(),
+                        _ => 0,
+                    },
+                    _ => return Err(String::from("wrong format")),
+                };
+
+                let mut args = vec![];
+                for _ in 0..arity {
+                    match parse_expression(token_iterator, brackets, function_map) {
+                        Ok(Node::Newline) | Ok(Node::Empty) => {
+                            return Err(String::from("not enough args for defined func"))
                         }
+                        Ok(arg) => args.push(arg),
+                        Err(_) => return Err(String::from("cannot parse arg of defined func")),
                     }
-                    Ok(Node::Caller {
-                        name: k.to_string(),
-                        args: Box::new(Node::Body(args)),
-                    })
-                } else {
-                    Err(String::from("wrong format"))
                 }
+                Ok(Node::Caller {
+                    name: k.to_string(),
+                    args: Box::new(Node::Body(args)),
+                })
             }
         }
     } else {
@@ -279,16 +590,63 @@ fn parse_command(
     }
 }
 
+///
+/// Parse an argument of an operation or command
+/// A '[' in this position opens a data list rather than a codeblock,
+/// anything else is parsed as a command
+/// 
+fn parse_expression(
+    token_iterator: &mut Peekable<std::vec::IntoIter<Token>>,
+    brackets: &mut i32,
+    function_map: &mut HashMap<String, Node>,
+) -> ParseResult<Node> {
+    if token_iterator.peek().map(Token::as_str) == Some("[") {
+        token_iterator.next();
+        parse_list(token_iterator)
+    } else {
+        parse_command(token_iterator, brackets, function_map)
+    }
+}
+
+///
+/// parse the items of a data list up to its closing bracket
+/// items are kept as literal words, including any " or : prefix
+/// 
+fn parse_list(token_iterator: &mut Peekable<std::vec::IntoIter<Token>>) -> ParseResult<Node> {
+    let mut items = vec![];
+    loop {
+        match token_iterator.next() {
+            Some(token) => match token.as_str() {
+                "]" => break,
+                "[" => match parse_list(token_iterator) {
+                    Ok(list) => items.push(list),
+                    Err(e) => return Err(e),
+                },
+                "\n" => return Err(String::from("Unterminated list")),
+                "\"" | ":" => match token_iterator.next() {
+                    Some(word) if word.as_str() != "\n" => {
+                        items.push(Node::Literal(token.value + word.as_str()))
+                    }
+                    _ => return Err(String::from("Unterminated list")),
+                },
+                _ => items.push(Node::Literal(token.value)),
+            },
+            None => return Err(String::from("Unterminated list")),
+        }
+    }
+    Ok(Node::List(items))
+}
+
 ///
 /// parse the unary expression
 /// 
 fn parse_unary(
     procedure: UnaryOp,
-    token_iterator: &mut Peekable<std::vec::IntoIter<String>>,
+    token_iterator: &mut Peekable<std::vec::IntoIter<Token>>,
     brackets: &mut i32,
     function_map: &mut HashMap<String, Node>,
 ) -> ParseResult<Node> {
-    if let Ok(expr) = parse_command(token_iterator, brackets, function_map) {
+    if let Ok(expr) = parse_expression(token_iterator, brackets, function_map) {
         Ok(Node::UnaryExpr {
             op: procedure,
             child: Box::new(expr),
@@ -298,6 +656,45 @@ fn parse_unary(
     }
 }
 
+///
+/// parse a unary expression that takes a colour
+/// colours written out in full (a literal or a list of literals) are checked
+/// here, whereas anything else can only be checked once it is evaluated
+/// 
+fn parse_colour(
+    procedure: UnaryOp,
+    token_iterator: &mut Peekable<std::vec::IntoIter<Token>>,
+    brackets: &mut i32,
+    function_map: &mut HashMap<String, Node>,
+) -> ParseResult<Node> {
+    match parse_unary(procedure, token_iterator, brackets, function_map) {
+        Ok(Node::UnaryExpr { op, child }) => {
+            let valid = match child.as_ref() {
+                Node::Literal(word) => Colour::from_word(word).is_some(),
+                Node::List(items) => {
+                    let components: Option<Vec<f64>> = items
+                        .iter()
+                        .map(|item| match item {
+                            Node::Literal(word) => word.parse::<f64>().ok(),
+                            _ => None,
+                        })
+                        .collect();
+                    components
+                        .and_then(|c| Colour::from_components(&c))
+                        .is_some()
+                }
+                _ => true,
+            };
+            if valid {
+                Ok(Node::UnaryExpr { op, child })
+            } else {
+                Err(String::from("Invalid colour"))
+            }
+        }
+        res => res,
+    }
+}
+
 ///
 /// parse the defined function node
 /// argument is on lhs as vector of Nodes
@@ -305,7 +702,7 @@ fn parse_unary(
 /// 
 fn parse_functional_binary(
     procedure: BinaryOp,
-    token_iterator: &mut Peekable<std::vec::IntoIter<String>>,
+    token_iterator: &mut Peekable<std::vec::IntoIter<Token>>,
     brackets: &mut i32,
     function_map: &mut HashMap<String, Node>,
 ) -> ParseResult<Node> {
@@ -327,6 +724,19 @@ fn parse_functional_binary(
             return Err(String::from("cannot parse arg of defined func"));
         }
     }
+
+    // register the function before parsing its body so it can call itself
+    if let BinaryOp::Func(name) = &procedure {
+        function_map.insert(
+            name.to_string(),
+            Node::BinaryExpr {
+                op: procedure.clone(),
+                lhs: Box::new(Node::Body(args.clone())),
+                rhs: Box::new(Node::Body(vec![])),
+            },
+        );
+    }
+
     match parse_logo(token_iterator, brackets, function_map) {
         Ok(body) => Ok(Node::BinaryExpr {
             op: procedure,
@@ -341,19 +751,39 @@ fn parse_functional_binary(
 /// parsing if and while nodes
 /// lhs has the condition and rhs has the body
 /// 
+/// Note that an 'if' body directly followed by ELSE on the same line
+/// (i.e. `] ELSE [`) is parsed as an ifelse node instead
+/// 
 fn parse_conditional_binary(
     procedure: BinaryOp,
-    token_iterator: &mut Peekable<std::vec::IntoIter<String>>,
+    token_iterator: &mut Peekable<std::vec::IntoIter<Token>>,
     brackets: &mut i32,
     function_map: &mut HashMap<String, Node>,
 ) -> ParseResult<Node> {
     if let Ok(cond) = parse_command(token_iterator, brackets, function_map) {
         match parse_logo(token_iterator, brackets, function_map) {
-            Ok(body) => Ok(Node::BinaryExpr {
-                op: procedure,
-                lhs: Box::new(cond),
-                rhs: Box::new(Node::Body(body)),
-            }),
+            Ok(body) => {
+                let has_else = matches!(procedure, BinaryOp::If)
+                    && token_iterator.peek().map(Token::as_str) == Some("ELSE");
+                if !has_else {
+                    return Ok(Node::BinaryExpr {
+                        op: procedure,
+                        lhs: Box::new(cond),
+                        rhs: Box::new(Node::Body(body)),
+                    });
+                }
+
+                token_iterator.next();
+                match parse_logo(token_iterator, brackets, function_map) {
+                    Ok(else_body) => Ok(Node::TernaryExpr {
+                        op: TernaryOp::IfElse,
+                        first: Box::new(cond),
+                        second: Box::new(Node::Body(body)),
+                        third: Box::new(Node::Body(else_body)),
+                    }),
+                    Err(e) => Err(e),
+                }
+            }
             Err(e) => Err(e),
         }
     } else {
@@ -361,17 +791,112 @@ fn parse_conditional_binary(
     }
 }
 
+///
+/// parsing ifelse nodes
+/// first has the condition, second has the body run when the condition
+/// holds and third has the body run otherwise
+/// 
+fn parse_conditional_ternary(
+    procedure: TernaryOp,
+    token_iterator: &mut Peekable<std::vec::IntoIter<Token>>,
+    brackets: &mut i32,
+    function_map: &mut HashMap<String, Node>,
+) -> ParseResult<Node> {
+    if let Ok(cond) = parse_command(token_iterator, brackets, function_map) {
+        match parse_logo(token_iterator, brackets, function_map) {
+            Ok(then_body) => match parse_logo(token_iterator, brackets, function_map) {
+                Ok(else_body) => Ok(Node::TernaryExpr {
+                    op: procedure,
+                    first: Box::new(cond),
+                    second: Box::new(Node::Body(then_body)),
+                    third: Box::new(Node::Body(else_body)),
+                }),
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        }
+    } else {
+        Err(String::from("Invalid cond"))
+    }
+}
+
+///
+/// parsing for nodes written as FOR [var start end step] [ body ]
+/// where step is optional but cannot be a literal zero
+/// 
+fn parse_for(
+    token_iterator: &mut Peekable<std::vec::IntoIter<Token>>,
+    brackets: &mut i32,
+    function_map: &mut HashMap<String, Node>,
+) -> ParseResult<Node> {
+    if token_iterator.next().as_ref().map(Token::as_str) != Some("[") {
+        return Err(String::from("Invalid for range"));
+    }
+
+    // the loop variable may be written bare or quoted
+    let var = match token_iterator.next() {
+        Some(token) if token.as_str() == "\"" => token_iterator.next(),
+        token => token,
+    };
+    let var = match var {
+        Some(name) if !matches!(name.as_str(), "[" | "]" | "\n" | ":") => name.value,
+        _ => return Err(String::from("Invalid for variable")),
+    };
+
+    let start = match parse_command(token_iterator, brackets, function_map) {
+        Ok(Node::Empty) | Ok(Node::Newline) | Err(_) => {
+            return Err(String::from("Invalid for start"))
+        }
+        Ok(expr) => expr,
+    };
+    let end = match parse_command(token_iterator, brackets, function_map) {
+        Ok(Node::Empty) | Ok(Node::Newline) | Err(_) => {
+            return Err(String::from("Invalid for end"))
+        }
+        Ok(expr) => expr,
+    };
+
+    let step = if token_iterator.peek().map(Token::as_str) == Some("]") {
+        None
+    } else {
+        match parse_command(token_iterator, brackets, function_map) {
+            Ok(Node::Literal(value)) if value.parse::<f64>() == Ok(0.0) => {
+                return Err(String::from("For step cannot be zero"))
+            }
+            Ok(Node::Empty) | Ok(Node::Newline) | Err(_) => {
+                return Err(String::from("Invalid for step"))
+            }
+            Ok(expr) => Some(Box::new(expr)),
+        }
+    };
+
+    if token_iterator.next().as_ref().map(Token::as_str) != Some("]") {
+        return Err(String::from("Invalid for range"));
+    }
+
+    match parse_logo(token_iterator, brackets, function_map) {
+        Ok(body) => Ok(Node::For {
+            var,
+            start: Box::new(start),
+            end: Box::new(end),
+            step,
+            body: Box::new(Node::Body(body)),
+        }),
+        Err(e) => Err(e),
+    }
+}
+
 /// 
 /// parse binary expressions
 /// 
 fn parse_binary(
     procedure: BinaryOp,
-    token_iterator: &mut Peekable<std::vec::IntoIter<String>>,
+    token_iterator: &mut Peekable<std::vec::IntoIter<Token>>,
     brackets: &mut i32,
     function_map: &mut HashMap<String, Node>,
 ) -> ParseResult<Node> {
-    if let Ok(expr1) = parse_command(token_iterator, brackets, function_map) {
-        if let Ok(expr2) = parse_command(token_iterator, brackets, function_map) {
+    if let Ok(expr1) = parse_expression(token_iterator, brackets, function_map) {
+        if let Ok(expr2) = parse_expression(token_iterator, brackets, function_map) {
             Ok(Node::BinaryExpr {
                 op: procedure,
                 lhs: Box::new(expr1),
@@ -389,15 +914,578 @@ fn parse_binary(
 /// parse literals and variables
 /// 
 fn parse_leaf(
-    token_iterator: &mut Peekable<std::vec::IntoIter<String>>,
+    token_iterator: &mut Peekable<std::vec::IntoIter<Token>>,
     leaf: Leaf,
 ) -> ParseResult<Node> {
     if let Some(res) = token_iterator.next() {
         match leaf {
-            Leaf::Literal => Ok(Node::Literal(res.to_string())),
-            Leaf::Variable => Ok(Node::Variable(res.to_string())),
+            Leaf::Literal => Ok(Node::Literal(res.value)),
+            Leaf::Variable => Ok(Node::Variable(res.value, Scope::Unresolved)),
         }
     } else {
         Err(String::from("Invalid arg"))
     }
 }
+
+///
+/// Parses all of the given tokens into an AST, checking that every
+/// codeblock is closed and that OUTPUT and STOP are only used inside
+/// defined functions
+/// 
+pub fn parse_program(tokens: Vec<Token>) -> ParseResult<Vec<Node>> {
+    let mut token_iterator = tokens.into_iter().peekable();
+    let mut brackets = 0; // this validates the start and end of each codeblock
+
+    // Function map is used to keep track of any defined functions where the key is
+    // the name of the function and value points to the AST root node of that function
+    let mut function_map: HashMap<String, Node> = HashMap::new();
+
+    match parse_logo(&mut token_iterator, &mut brackets, &mut function_map) {
+        Ok(ast) => {
+            if brackets != 0 {
+                return Err(String::from("Invalid codeblock"));
+            }
+            match check_function_returns(&ast) {
+                Ok(()) => Ok(ast),
+                Err(e) => Err(e),
+            }
+        }
+        Err(e) => Err(e),
+    }
+}
+
+///
+/// Checks that OUTPUT and STOP are only used inside a defined function
+/// The bodies of defined functions are skipped, whereas every other node
+/// is checked, including the inputs of calls and the range of FOR loops
+/// 
+pub fn check_function_returns(ast: &[Node]) -> ParseResult<()> {
+    let mut checker = ReturnChecker { error: None };
+    walk_nodes(&mut checker, ast);
+    match checker.error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+///
+/// Struct representing a visitor that finds the first OUTPUT or STOP
+/// outside of the body of a defined function
+///
+struct ReturnChecker {
+    error: Option<String>,
+}
+
+impl Visitor for ReturnChecker {
+    fn visit_leaf(&mut self, node: &Node) {
+        if let Node::Stop = node {
+            self.error
+                .get_or_insert(String::from("STOP can only be used inside a function"));
+        }
+    }
+
+    fn visit_unary(&mut self, op: &UnaryOp, child: &Node) {
+        if let UnaryOp::Output = op {
+            self.error
+                .get_or_insert(String::from("OUTPUT can only be used inside a function"));
+        }
+        walk_unary(self, op, child);
+    }
+
+    fn visit_function(&mut self, _name: &str, _params: &Node, _body: &Node) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenise_source;
+
+    fn parse(source: &str) -> ParseResult<Vec<Node>> {
+        parse_program(tokenise_source(source).unwrap())
+    }
+
+    ///
+    /// Parses the code, returning the node of its last statement
+    /// with the spans cleared
+    ///
+    fn parse_last(source: &str) -> Node {
+        match parse(source).unwrap().last() {
+            Some(Node::Statement { node, .. }) => node.without_spans(),
+            node => panic!("expected a statement, found {node:?}"),
+        }
+    }
+
+    fn lit(word: &str) -> Node {
+        Node::Literal(word.to_string())
+    }
+
+    fn var(name: &str) -> Node {
+        Node::Variable(name.to_string(), Scope::Unresolved)
+    }
+
+    fn unary(op: UnaryOp, child: Node) -> Node {
+        Node::UnaryExpr {
+            op,
+            child: Box::new(child),
+        }
+    }
+
+    fn binary(op: BinaryOp, lhs: Node, rhs: Node) -> Node {
+        Node::BinaryExpr {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        }
+    }
+
+    ///
+    /// Returns a codeblock of the nodes, each in a statement with no span
+    ///
+    fn block(nodes: Vec<Node>) -> Node {
+        Node::Body(
+            nodes
+                .into_iter()
+                .map(|node| Node::Statement {
+                    span: Span::default(),
+                    node: Box::new(node),
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn parses_output_and_stop_in_functions() {
+        let source = "TO sq :x\n    IF EQ :x \"0 [\n        STOP\n    ]\n    OUTPUT * :x :x\nEND\n";
+        let stop = binary(
+            BinaryOp::If,
+            binary(BinaryOp::Eq, var("x"), lit("0")),
+            block(vec![Node::Stop]),
+        );
+        let output = unary(UnaryOp::Output, binary(BinaryOp::Mul, var("x"), var("x")));
+        assert_eq!(
+            parse_last(source),
+            binary(
+                BinaryOp::Func(String::from("sq")),
+                Node::Body(vec![var("x")]),
+                block(vec![stop, output])
+            )
+        );
+    }
+
+    #[test]
+    fn parses_calls_inside_expressions() {
+        let source = "TO sq :x\n    OUTPUT * :x :x\nEND\nFORWARD sq \"3\n";
+        let call = Node::Caller {
+            name: String::from("sq"),
+            args: Box::new(Node::Body(vec![lit("3")])),
+        };
+        assert_eq!(parse_last(source), unary(UnaryOp::Forward, call));
+    }
+
+    #[test]
+    fn rejects_output_and_stop_outside_functions() {
+        let output = Err(String::from("OUTPUT can only be used inside a function"));
+        let stop = Err(String::from("STOP can only be used inside a function"));
+        assert_eq!(parse("OUTPUT \"1\n"), output);
+        assert_eq!(parse("STOP\n"), stop);
+        assert_eq!(parse("IF EQ \"1 \"1 [\n    STOP\n]\n"), stop);
+        assert_eq!(
+            parse("TO f :a\n    FORWARD :a\nEND\nf OUTPUT \"1\n"),
+            output
+        );
+        assert_eq!(parse("FOR [i OUTPUT \"1 \"3] [\n]\n"), output);
+        assert_eq!(parse("FOR [i \"1 \"3 OUTPUT \"1] [\n]\n"), output);
+    }
+
+    #[test]
+    fn parses_each_form_of_colour() {
+        for colour in ["\"4", "\"4.0", "[255 0 0]", "\"#ff0000", "\"red", ":c"] {
+            let child = match parse_last(&format!("SETPENCOLOR {colour}\n")) {
+                Node::UnaryExpr {
+                    op: UnaryOp::Setpencolor,
+                    child,
+                } => child,
+                node => panic!("expected SETPENCOLOR, found {node:?}"),
+            };
+            let expected = match colour {
+                "[255 0 0]" => Node::List(vec![lit("255"), lit("0"), lit("0")]),
+                ":c" => var("c"),
+                _ => lit(&colour[1..]),
+            };
+            assert_eq!(*child, expected);
+        }
+        assert_eq!(
+            parse_last("SETBACKGROUND \"blue\n"),
+            unary(UnaryOp::Setbackground, lit("blue"))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_colours() {
+        for colour in ["\"16", "\"4.5", "[255 0]", "[256 0 0]", "\"#ff00", "\"pink"] {
+            assert_eq!(
+                parse(&format!("SETPENCOLOR {colour}\n")),
+                Err(String::from("Invalid colour")),
+                "{colour}"
+            );
+        }
+    }
+
+    #[test]
+    fn parses_ifelse() {
+        let source = "IFELSE EQ :x \"1 [\n    PRINT \"a\n] [\n    PRINT \"b\n]\n";
+        let expected = Node::TernaryExpr {
+            op: TernaryOp::IfElse,
+            first: Box::new(binary(BinaryOp::Eq, var("x"), lit("1"))),
+            second: Box::new(block(vec![unary(UnaryOp::Print, lit("a"))])),
+            third: Box::new(block(vec![unary(UnaryOp::Print, lit("b"))])),
+        };
+        assert_eq!(parse_last(source), expected);
+
+        // ELSE straight after the body of an IF makes the same node
+        let source = "IF EQ :x \"1 [\n    PRINT \"a\n] ELSE [\n    PRINT \"b\n]\n";
+        assert_eq!(parse_last(source), expected);
+    }
+
+    #[test]
+    fn only_joins_else_to_if_on_the_same_line() {
+        let source = "IF EQ :x \"1 [\n    PRINT \"a\n]\n";
+        assert_eq!(
+            parse_last(source),
+            binary(
+                BinaryOp::If,
+                binary(BinaryOp::Eq, var("x"), lit("1")),
+                block(vec![unary(UnaryOp::Print, lit("a"))])
+            )
+        );
+        let source = "IF EQ :x \"1 [\n    PRINT \"a\n]\nELSE [\n    PRINT \"b\n]\n";
+        assert_eq!(parse(source), Err(String::from("wrong format")));
+        let source = "WHILE EQ :x \"1 [\n    PRINT \"a\n] ELSE [\n    PRINT \"b\n]\n";
+        assert_eq!(parse(source), Err(String::from("Invalid number of args")));
+    }
+
+    fn for_loop(var: &str, start: Node, end: Node, step: Option<Node>, body: Node) -> Node {
+        Node::For {
+            var: var.to_string(),
+            start: Box::new(start),
+            end: Box::new(end),
+            step: step.map(Box::new),
+            body: Box::new(body),
+        }
+    }
+
+    #[test]
+    fn parses_for_loops() {
+        let source = "FOR [i \"1 \"5] [\n    PRINT :i\n]\n";
+        assert_eq!(
+            parse_last(source),
+            for_loop(
+                "i",
+                lit("1"),
+                lit("5"),
+                None,
+                block(vec![unary(UnaryOp::Print, var("i"))])
+            )
+        );
+
+        // the variable can be quoted and the step can be negative
+        let source = "FOR [\"i \"5 \"1 \"-2] [\n]\n";
+        let expected = for_loop("i", lit("5"), lit("1"), Some(lit("-2")), block(vec![]));
+        assert_eq!(parse_last(source), expected);
+
+        // a step that is only known once it is run is not checked
+        let source = "FOR [i \"1 \"5 - \"1 \"1] [\n]\n";
+        let step = binary(BinaryOp::Sub, lit("1"), lit("1"));
+        let expected = for_loop("i", lit("1"), lit("5"), Some(step), block(vec![]));
+        assert_eq!(parse_last(source), expected);
+    }
+
+    #[test]
+    fn rejects_invalid_for_loops() {
+        let cases = [
+            ("FOR [i \"1 \"5 \"0] [\n]\n", "For step cannot be zero"),
+            ("FOR [i \"1 \"5 \"0.0] [\n]\n", "For step cannot be zero"),
+            ("FOR [:i \"1 \"5] [\n]\n", "Invalid for variable"),
+            ("FOR [i \"1] [\n]\n", "Invalid for end"),
+            ("FOR i \"1 \"5 [\n]\n", "Invalid for range"),
+            ("FOR [i \"1 \"5 \"1 \"2] [\n]\n", "Invalid for range"),
+        ];
+        for (source, error) in cases {
+            assert_eq!(parse(source), Err(String::from(error)), "{source}");
+        }
+    }
+
+    #[test]
+    fn parses_logical_operators() {
+        assert_eq!(
+            parse_last("PRINT NOT GE :x \"1\n"),
+            unary(
+                UnaryOp::Print,
+                unary(UnaryOp::Not, binary(BinaryOp::Ge, var("x"), lit("1")))
+            )
+        );
+        assert_eq!(
+            parse_last("PRINT XOR LE \"1 \"2 NOT EQ \"1 \"1\n"),
+            unary(
+                UnaryOp::Print,
+                binary(
+                    BinaryOp::Xor,
+                    binary(BinaryOp::Le, lit("1"), lit("2")),
+                    unary(UnaryOp::Not, binary(BinaryOp::Eq, lit("1"), lit("1")))
+                )
+            )
+        );
+    }
+
+    #[test]
+    fn parses_math_operations_with_their_arities() {
+        let unary_ops = [
+            UnaryOp::Sqrt,
+            UnaryOp::Abs,
+            UnaryOp::Int,
+            UnaryOp::Round,
+            UnaryOp::Sin,
+            UnaryOp::Cos,
+            UnaryOp::Tan,
+            UnaryOp::Arctan,
+            UnaryOp::Random,
+        ];
+        for op in unary_ops {
+            let source = format!("PRINT {} \"1\n", op.keyword());
+            assert_eq!(
+                parse_last(&source),
+                unary(UnaryOp::Print, unary(op.clone(), lit("1")))
+            );
+            let source = format!("PRINT {} \"1 \"2\n", op.keyword());
+            assert_eq!(parse(&source), Err(String::from("Invalid number of args")));
+        }
+
+        for op in [BinaryOp::Power, BinaryOp::Modulo, BinaryOp::Remainder] {
+            let source = format!("PRINT {} \"1 \"2\n", op.keyword());
+            assert_eq!(
+                parse_last(&source),
+                unary(UnaryOp::Print, binary(op.clone(), lit("1"), lit("2")))
+            );
+            let source = format!("PRINT {} \"1 \"2 \"3\n", op.keyword());
+            assert_eq!(parse(&source), Err(String::from("Invalid number of args")));
+        }
+
+        assert_eq!(
+            parse_last("PRINT + PI \"1\n"),
+            unary(UnaryOp::Print, binary(BinaryOp::Add, Node::Pi, lit("1")))
+        );
+        assert_eq!(
+            parse("PRINT PI \"1\n"),
+            Err(String::from("Invalid number of args"))
+        );
+    }
+
+    #[test]
+    fn parses_data_lists() {
+        // items keep any quote or colon, and lists can be nested or empty
+        assert_eq!(
+            parse_last("MAKE \"l [1 [2 []] \"a :b]\n"),
+            binary(
+                BinaryOp::Make,
+                lit("l"),
+                Node::List(vec![
+                    lit("1"),
+                    Node::List(vec![lit("2"), Node::List(vec![])]),
+                    lit("\"a"),
+                    lit(":b"),
+                ])
+            )
+        );
+        assert_eq!(
+            parse_last("SHOW SENTENCE LIST \"1 \"2 FPUT \"0 LPUT \"4 [3]\n"),
+            unary(
+                UnaryOp::Show,
+                binary(
+                    BinaryOp::Sentence,
+                    binary(BinaryOp::List, lit("1"), lit("2")),
+                    binary(
+                        BinaryOp::Fput,
+                        lit("0"),
+                        binary(BinaryOp::Lput, lit("4"), Node::List(vec![lit("3")]))
+                    )
+                )
+            )
+        );
+        assert_eq!(
+            parse_last("PRINT ITEM \"2 BUTFIRST BUTLAST [a b c]\n"),
+            unary(
+                UnaryOp::Print,
+                binary(
+                    BinaryOp::Item,
+                    lit("2"),
+                    unary(
+                        UnaryOp::Butfirst,
+                        unary(
+                            UnaryOp::Butlast,
+                            Node::List(vec![lit("a"), lit("b"), lit("c")])
+                        )
+                    )
+                )
+            )
+        );
+    }
+
+    #[test]
+    fn tells_data_lists_from_codeblocks() {
+        // the condition is an expression, so its [ opens a data list,
+        // whereas the [ after it opens the body
+        assert_eq!(
+            parse_last("IF EMPTYP [] [\n    PRINT COUNT [1 2]\n]\n"),
+            binary(
+                BinaryOp::If,
+                unary(UnaryOp::Emptyp, Node::List(vec![])),
+                block(vec![unary(
+                    UnaryOp::Print,
+                    unary(UnaryOp::Count, Node::List(vec![lit("1"), lit("2")]))
+                )])
+            )
+        );
+
+        // a data list has to be closed on the line it is opened on
+        assert_eq!(
+            parse("PRINT COUNT [1 2\n"),
+            Err(String::from("Invalid args unary"))
+        );
+    }
+
+    #[test]
+    fn parses_word_operations() {
+        let unary_ops = [
+            UnaryOp::Uppercase,
+            UnaryOp::Lowercase,
+            UnaryOp::Numberp,
+            UnaryOp::Wordp,
+            UnaryOp::Char,
+            UnaryOp::Ascii,
+            UnaryOp::Count,
+            UnaryOp::First,
+            UnaryOp::Butfirst,
+        ];
+        for op in unary_ops {
+            let source = format!("PRINT {} \"abc\n", op.keyword());
+            assert_eq!(
+                parse_last(&source),
+                unary(UnaryOp::Print, unary(op.clone(), lit("abc")))
+            );
+            let source = format!("PRINT {} \"a \"b\n", op.keyword());
+            assert_eq!(parse(&source), Err(String::from("Invalid number of args")));
+        }
+
+        assert_eq!(
+            parse_last("PRINT MEMBERP \"b WORD \"a :x\n"),
+            unary(
+                UnaryOp::Print,
+                binary(
+                    BinaryOp::Memberp,
+                    lit("b"),
+                    binary(BinaryOp::Word, lit("a"), var("x"))
+                )
+            )
+        );
+    }
+
+    #[test]
+    fn parses_print_show_and_type() {
+        for op in [UnaryOp::Print, UnaryOp::Show, UnaryOp::Type] {
+            let source = format!("{} [1 [2]]\n", op.keyword());
+            let list = Node::List(vec![lit("1"), Node::List(vec![lit("2")])]);
+            assert_eq!(parse_last(&source), unary(op.clone(), list));
+            let source = format!("{} + :x \"1\n", op.keyword());
+            let sum = binary(BinaryOp::Add, var("x"), lit("1"));
+            assert_eq!(parse_last(&source), unary(op.clone(), sum));
+            let source = format!("{} \"a \"b\n", op.keyword());
+            assert_eq!(parse(&source), Err(String::from("Invalid number of args")));
+        }
+    }
+
+    #[test]
+    fn parses_turtle_commands() {
+        for node in [Node::Home, Node::Clearscreen, Node::Clean] {
+            let keyword = node.keyword().unwrap();
+            assert_eq!(parse_last(&format!("{keyword}\n")), node);
+            let source = format!("{keyword} \"1\n");
+            assert_eq!(parse(&source), Err(String::from("Invalid number of args")));
+        }
+
+        for op in [BinaryOp::Setxy, BinaryOp::Arc] {
+            let source = format!("{} \"90 :r\n", op.keyword());
+            assert_eq!(parse_last(&source), binary(op.clone(), lit("90"), var("r")));
+            let source = format!("{} \"1 \"2 \"3\n", op.keyword());
+            assert_eq!(parse(&source), Err(String::from("Invalid number of args")));
+        }
+
+        let position = || Node::List(vec![lit("1"), lit("2")]);
+        assert_eq!(
+            parse_last("SETPOS [1 2]\n"),
+            unary(UnaryOp::Setpos, position())
+        );
+        assert_eq!(parse_last("CIRCLE \"5\n"), unary(UnaryOp::Circle, lit("5")));
+        assert_eq!(
+            parse("CIRCLE \"5 \"6\n"),
+            Err(String::from("Invalid number of args"))
+        );
+    }
+
+    #[test]
+    fn parses_position_queries() {
+        for op in [UnaryOp::Towards, UnaryOp::Distance] {
+            let source = format!("PRINT {} [1 2]\n", op.keyword());
+            let position = Node::List(vec![lit("1"), lit("2")]);
+            assert_eq!(
+                parse_last(&source),
+                unary(UnaryOp::Print, unary(op.clone(), position))
+            );
+            let source = format!("PRINT {} POS\n", op.keyword());
+            assert_eq!(
+                parse_last(&source),
+                unary(UnaryOp::Print, unary(op.clone(), Node::Pos))
+            );
+            let source = format!("PRINT {} \"1 \"2\n", op.keyword());
+            assert_eq!(parse(&source), Err(String::from("Invalid number of args")));
+        }
+    }
+
+    #[test]
+    fn parses_pen_attributes() {
+        let commands = [
+            Node::Penup,
+            Node::Pendown,
+            Node::Penpaint,
+            Node::Penerase,
+            Node::Penreverse,
+        ];
+        for node in commands {
+            let keyword = node.keyword().unwrap();
+            assert_eq!(parse_last(&format!("{keyword}\n")), node);
+            let source = format!("{keyword} \"1\n");
+            assert_eq!(parse(&source), Err(String::from("Invalid number of args")));
+        }
+        for node in [Node::Pensize, Node::Pendownp] {
+            let source = format!("PRINT {}\n", node.keyword().unwrap());
+            assert_eq!(parse_last(&source), unary(UnaryOp::Print, node));
+        }
+
+        assert_eq!(
+            parse_last("SETPENSIZE * PENSIZE \"2\n"),
+            unary(
+                UnaryOp::Setpensize,
+                binary(BinaryOp::Mul, Node::Pensize, lit("2"))
+            )
+        );
+        assert_eq!(
+            parse("SETPENSIZE \"1 \"2\n"),
+            Err(String::from("Invalid number of args"))
+        );
+
+        // SETBG is another name for SETBACKGROUND, which takes a colour
+        let expected = unary(UnaryOp::Setbackground, lit("red"));
+        assert_eq!(parse_last("SETBG \"red\n"), expected);
+        assert_eq!(parse_last("SETBACKGROUND \"red\n"), expected);
+        assert_eq!(parse("SETBG \"nope\n"), Err(String::from("Invalid colour")));
+    }
+}
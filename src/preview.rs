@@ -0,0 +1,220 @@
+use crate::colour::Colour;
+use crate::interpreter::{Drawing, PenMode};
+use std::fmt::Write;
+
+/// Each braille character is a grid of dots two wide and four tall
+const DOTS_PER_COLUMN: usize = 2;
+const DOTS_PER_ROW: usize = 4;
+
+///
+/// Bit of the braille character for the dot at (x, y) within a cell
+///
+const BRAILLE_BITS: [[u32; DOTS_PER_ROW]; DOTS_PER_COLUMN] =
+    [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+
+///
+/// Struct representing a grid of braille dots, where each dot that is
+/// lit stores its colour and the order it was drawn in
+///
+struct DotGrid {
+    width: usize,
+    height: usize,
+    dots: Vec<Option<(usize, Colour)>>,
+}
+
+impl DotGrid {
+    fn set(&mut self, x: usize, y: usize, dot: Option<(usize, Colour)>) {
+        if x < self.width && y < self.height {
+            self.dots[y * self.width + x] = dot;
+        }
+    }
+
+    fn get(&self, x: usize, y: usize) -> Option<(usize, Colour)> {
+        if x < self.width && y < self.height {
+            self.dots[y * self.width + x]
+        } else {
+            None
+        }
+    }
+}
+
+///
+/// This function is responsible for drawing a drawing in the terminal
+/// The visible lines are scaled to fit within the given number of columns
+/// and rows, and drawn with braille characters coloured with ANSI escapes
+/// The colour of each character is the colour of its most recently drawn dot
+///
+pub fn render_preview(drawing: &Drawing, columns: usize, rows: usize) -> String {
+    let lines: Vec<_> = drawing.visible_lines().collect();
+    if lines.is_empty() || columns == 0 || rows == 0 {
+        return String::new();
+    }
+
+    // bounds of everything drawn in turtle coordinates
+    let points = lines.iter().flat_map(|line| [line.from, line.to]);
+    let (mut min_x, mut max_x, mut min_y, mut max_y) = (f64::MAX, f64::MIN, f64::MAX, f64::MIN);
+    for (x, y) in points {
+        min_x = min_x.min(x);
+        max_x = max_x.max(x);
+        min_y = min_y.min(y);
+        max_y = max_y.max(y);
+    }
+
+    // dots are roughly square, so both axes share the same scale
+    let max_width = (columns * DOTS_PER_COLUMN - 1) as f64;
+    let max_height = (rows * DOTS_PER_ROW - 1) as f64;
+    let scale = f64::min(
+        max_width / (max_x - min_x).max(1.0),
+        max_height / (max_y - min_y).max(1.0),
+    );
+    let to_dot = |(x, y): (f64, f64)| {
+        (
+            ((x - min_x) * scale).round() as i64,
+            // y points up for the turtle but down the terminal
+            ((max_y - y) * scale).round() as i64,
+        )
+    };
+
+    let mut grid = DotGrid {
+        width: ((max_x - min_x) * scale).round() as usize + 1,
+        height: ((max_y - min_y) * scale).round() as usize + 1,
+        dots: vec![],
+    };
+    grid.dots = vec![None; grid.width * grid.height];
+
+    for (order, line) in lines.iter().enumerate() {
+        for (x, y) in dots_between(to_dot(line.from), to_dot(line.to)) {
+            let (x, y) = (x as usize, y as usize);
+            let dot = match line.mode {
+                PenMode::Paint => Some((order, line.colour)),
+                PenMode::Erase => None,
+                PenMode::Reverse => match grid.get(x, y) {
+                    Some(_) => None,
+                    None => Some((order, line.colour)),
+                },
+            };
+            grid.set(x, y, dot);
+        }
+    }
+
+    draw_grid(&grid)
+}
+
+///
+/// Returns every dot on the line between the two dots using
+/// Bresenham's line algorithm
+///
+fn dots_between((x1, y1): (i64, i64), (x2, y2): (i64, i64)) -> Vec<(i64, i64)> {
+    let (dx, dy) = ((x2 - x1).abs(), -(y2 - y1).abs());
+    let (step_x, step_y) = ((x2 - x1).signum(), (y2 - y1).signum());
+    let (mut x, mut y, mut error) = (x1, y1, dx + dy);
+    let mut dots = vec![(x, y)];
+
+    while (x, y) != (x2, y2) {
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+        dots.push((x, y));
+    }
+    dots
+}
+
+///
+/// Turns the grid of dots into rows of coloured braille characters
+///
+fn draw_grid(grid: &DotGrid) -> String {
+    let mut out = String::new();
+    let columns = grid.width.div_ceil(DOTS_PER_COLUMN);
+    let rows = grid.height.div_ceil(DOTS_PER_ROW);
+
+    for row in 0..rows {
+        let mut current: Option<Colour> = None;
+        for column in 0..columns {
+            let mut bits = 0;
+            let mut latest: Option<(usize, Colour)> = None;
+            for (dx, column_bits) in BRAILLE_BITS.iter().enumerate() {
+                for (dy, bit) in column_bits.iter().enumerate() {
+                    let dot = grid.get(column * DOTS_PER_COLUMN + dx, row * DOTS_PER_ROW + dy);
+                    if let Some((order, colour)) = dot {
+                        bits |= bit;
+                        if latest.is_none_or(|(latest_order, _)| order > latest_order) {
+                            latest = Some((order, colour));
+                        }
+                    }
+                }
+            }
+
+            match latest {
+                Some((_, colour)) => {
+                    if current != Some(colour) {
+                        let _ = write!(out, "\x1b[38;2;{};{};{}m", colour.r, colour.g, colour.b);
+                        current = Some(colour);
+                    }
+                    out.push(char::from_u32(0x2800 + bits).unwrap_or(' '));
+                }
+                None => out.push(' '),
+            }
+        }
+        if current.is_some() {
+            out.push_str("\x1b[0m");
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{DrawOp, Line};
+
+    fn line(from: (f64, f64), to: (f64, f64), colour: Colour) -> DrawOp {
+        DrawOp::Line(Line {
+            from,
+            to,
+            colour,
+            width: 1.0,
+            mode: PenMode::Paint,
+        })
+    }
+
+    #[test]
+    fn renders_fixed_drawing() {
+        let red = Colour::rgb(255, 0, 0);
+        let green = Colour::rgb(0, 255, 0);
+        let drawing = Drawing {
+            background: Colour::rgb(0, 0, 0),
+            ops: vec![
+                line((0.0, 0.0), (0.0, 70.0), red),
+                line((0.0, 70.0), (70.0, 70.0), red),
+                line((70.0, 70.0), (70.0, 0.0), red),
+                line((70.0, 0.0), (0.0, 0.0), red),
+                line((0.0, 0.0), (70.0, 70.0), green),
+            ],
+        };
+        // each character takes the colour of the last line drawn through it
+        let expected = "\x1b[38;2;255;0;0m⡏⠉\x1b[38;2;0;255;0m⡩⢻\x1b[0m\n\
+                        \x1b[38;2;0;255;0m⣧⣊\x1b[38;2;255;0;0m⣀⣸\x1b[0m\n";
+        assert_eq!(render_preview(&drawing, 4, 2), expected);
+        // both axes are scaled the same, so a wider terminal draws the same square
+        assert_eq!(render_preview(&drawing, 10, 2), expected);
+    }
+
+    #[test]
+    fn renders_nothing_without_visible_lines() {
+        let drawing = Drawing {
+            background: Colour::rgb(0, 0, 0),
+            ops: vec![
+                line((0.0, 0.0), (1.0, 1.0), Colour::rgb(255, 0, 0)),
+                DrawOp::Clear,
+            ],
+        };
+        assert_eq!(render_preview(&drawing, 4, 2), "");
+    }
+}
@@ -0,0 +1,544 @@
+use crate::ast::*;
+use std::collections::{HashSet, VecDeque};
+
+/// Each level of codeblock or function body is indented by this much
+const INDENT: &str = "    ";
+
+///
+/// This function is responsible for turning an AST back into logo code
+/// Every statement is written on its own line, with the body of each
+/// codeblock and function indented one level further than the line that
+/// opens it, and words are always written with the keyword used by the parser
+///
+/// Note that parsing the code again gives the same AST apart from spans,
+/// which record where each statement is in the printed code instead, and
+/// the scope of variables, which are Unresolved until resolved again
+///
+/// Note that IF ... ELSE is written as IFELSE, which parses to the same node
+///
+pub fn print_logo(ast: &[Node]) -> String {
+    print_with_layout(ast, Layout::default())
+}
+
+///
+/// Prints the AST the same way as print_logo, putting back the comments
+/// and blank lines of the code it was parsed from
+///
+pub(crate) fn print_with_layout(ast: &[Node], layout: Layout) -> String {
+    let mut printer = Printer {
+        layout,
+        opened: true,
+        ..Printer::default()
+    };
+    printer.statements(ast);
+    printer.comments_before(usize::MAX);
+    printer.out
+}
+
+///
+/// Struct representing what the AST does not store about the code it was
+/// parsed from, where comments are whole line comments in the order they
+/// were written, closers are the lines of every ']' and END in the order
+/// they were written and blank_lines are the lines with nothing on them
+///
+#[derive(Debug, Default)]
+pub(crate) struct Layout {
+    pub comments: VecDeque<(usize, String)>,
+    pub closers: Vec<usize>,
+    pub blank_lines: HashSet<usize>,
+}
+
+///
+/// Struct representing the logo code written so far, where depth is the
+/// number of codeblocks the printer is inside, closed counts the ']' and
+/// END written so far and opened is whether nothing has been written since
+/// the start of the code or of a codeblock
+///
+#[derive(Default)]
+struct Printer {
+    out: String,
+    depth: usize,
+    layout: Layout,
+    closed: usize,
+    opened: bool,
+}
+
+impl Printer {
+    fn statements(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            match node {
+                Node::Body(nodes) => self.statements(nodes),
+                Node::Empty | Node::Newline => {}
+                Node::Statement { span, node } => {
+                    self.comments_before(span.line);
+                    self.blank_line_before(span.line);
+                    self.statement(node);
+                }
+                node => self.statement(node),
+            }
+        }
+    }
+
+    ///
+    /// Writes every comment that was written before the given line
+    ///
+    fn comments_before(&mut self, line: usize) {
+        let is_before = |(comment_line, _): &(usize, String)| *comment_line < line;
+        while self.layout.comments.front().is_some_and(is_before) {
+            let Some((comment_line, comment)) = self.layout.comments.pop_front() else {
+                break;
+            };
+            self.blank_line_before(comment_line);
+            self.indent();
+            self.out.push_str(&comment);
+            self.out.push('\n');
+            self.opened = false;
+        }
+    }
+
+    ///
+    /// Keeps a single blank line where the code had any, apart from
+    /// at the start of the code or of a codeblock
+    ///
+    fn blank_line_before(&mut self, line: usize) {
+        if !self.opened && line > 1 && self.layout.blank_lines.contains(&(line - 1)) {
+            self.out.push('\n');
+        }
+    }
+
+    ///
+    /// Writes the comments inside a codeblock that come after its last
+    /// statement, which are the ones before its ']' or END
+    ///
+    fn close(&mut self) {
+        if let Some(line) = self.layout.closers.get(self.closed).copied() {
+            self.comments_before(line);
+        }
+        self.closed += 1;
+    }
+
+    fn statement(&mut self, node: &Node) {
+        self.indent();
+        self.node(node);
+        self.out.push('\n');
+        self.opened = false;
+    }
+
+    ///
+    /// Writes a codeblock with its statements on their own lines,
+    /// leaving the closing bracket at the end of the output
+    ///
+    fn block(&mut self, body: &Node) {
+        self.out.push_str("[\n");
+        self.depth += 1;
+        self.opened = true;
+        self.statements(std::slice::from_ref(body));
+        self.close();
+        self.depth -= 1;
+        self.indent();
+        self.out.push(']');
+    }
+
+    fn indent(&mut self) {
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    fn word(&mut self, word: &str) {
+        self.out.push(' ');
+        self.out.push_str(word);
+    }
+
+    ///
+    /// Writes a node after a space, as an argument of the node before it
+    ///
+    fn argument(&mut self, node: &Node) {
+        self.out.push(' ');
+        self.node(node);
+    }
+
+    fn node(&mut self, node: &Node) {
+        match node {
+            Node::Literal(word) => {
+                self.out.push('"');
+                self.out.push_str(word);
+            }
+            Node::Variable(name, _) => {
+                self.out.push(':');
+                self.out.push_str(name);
+            }
+            Node::List(items) => {
+                self.out.push('[');
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        self.out.push(' ');
+                    }
+                    match item {
+                        // items of a data list are written without a prefix
+                        Node::Literal(word) => self.out.push_str(word),
+                        item => self.node(item),
+                    }
+                }
+                self.out.push(']');
+                self.closed += 1;
+            }
+            Node::UnaryExpr { op, child } => {
+                self.out.push_str(op.keyword());
+                self.argument(child);
+            }
+            Node::BinaryExpr {
+                op: BinaryOp::Func(name),
+                lhs,
+                rhs,
+            } => {
+                self.out.push_str("TO");
+                self.word(name);
+                if let Node::Body(params) = lhs.as_ref() {
+                    for param in params {
+                        self.argument(param);
+                    }
+                }
+                self.out.push('\n');
+                self.depth += 1;
+                self.opened = true;
+                self.statements(std::slice::from_ref(rhs));
+                self.close();
+                self.depth -= 1;
+                self.indent();
+                self.out.push_str("END");
+            }
+            Node::BinaryExpr {
+                op: op @ (BinaryOp::If | BinaryOp::While),
+                lhs,
+                rhs,
+            } => {
+                self.out.push_str(op.keyword());
+                self.argument(lhs);
+                self.out.push(' ');
+                self.block(rhs);
+            }
+            Node::BinaryExpr { op, lhs, rhs } => {
+                self.out.push_str(op.keyword());
+                self.argument(lhs);
+                self.argument(rhs);
+            }
+            Node::TernaryExpr {
+                op,
+                first,
+                second,
+                third,
+            } => {
+                self.out.push_str(op.keyword());
+                self.argument(first);
+                self.out.push(' ');
+                self.block(second);
+                self.out.push(' ');
+                self.block(third);
+            }
+            Node::For {
+                var,
+                start,
+                end,
+                step,
+                body,
+            } => {
+                self.out.push_str("FOR [");
+                self.out.push_str(var);
+                self.argument(start);
+                self.argument(end);
+                if let Some(step) = step {
+                    self.argument(step);
+                }
+                self.out.push_str("] ");
+                self.closed += 1;
+                self.block(body);
+            }
+            Node::Body(nodes) => {
+                for (index, node) in nodes.iter().enumerate() {
+                    if index > 0 {
+                        self.out.push(' ');
+                    }
+                    self.node(node);
+                }
+            }
+            Node::Statement { node, .. } => self.node(node),
+            Node::Empty | Node::Newline => {}
+            Node::Caller { name, args } => {
+                self.out.push_str(name);
+                if let Node::Body(args) = args.as_ref() {
+                    for arg in args {
+                        self.argument(arg);
+                    }
+                }
+            }
+            node => self.out.push_str(node.keyword().unwrap_or_default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenise_source;
+    use crate::parser::parse_program;
+    use std::fs;
+    use std::path::Path;
+
+    fn parse(source: &str) -> Vec<Node> {
+        let tokens = tokenise_source(source).unwrap();
+        parse_program(tokens).unwrap_or_else(|e| panic!("{e} in\n{source}"))
+    }
+
+    ///
+    /// Asserts that parsing the printed AST gives back the expected AST
+    ///
+    fn assert_round_trip(ast: &[Node], expected: &[Node]) {
+        let printed = print_logo(ast);
+        let parsed = parse(&printed);
+        assert!(
+            Node::Body(parsed.clone()).eq_ignoring_spans(&Node::Body(expected.to_vec())),
+            "printed\n{printed}\nparsed {parsed:?}\nexpected {expected:?}"
+        );
+    }
+
+    fn statement(node: Node) -> Node {
+        Node::Statement {
+            span: Span::default(),
+            node: Box::new(node),
+        }
+    }
+
+    fn lit(word: &str) -> Node {
+        Node::Literal(word.to_string())
+    }
+
+    fn var(name: &str) -> Node {
+        Node::Variable(name.to_string(), Scope::Unresolved)
+    }
+
+    fn unary(op: UnaryOp, child: Node) -> Node {
+        Node::UnaryExpr {
+            op,
+            child: Box::new(child),
+        }
+    }
+
+    fn binary(op: BinaryOp, lhs: Node, rhs: Node) -> Node {
+        Node::BinaryExpr {
+            op,
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+        }
+    }
+
+    fn body(nodes: Vec<Node>) -> Node {
+        Node::Body(nodes.into_iter().map(statement).collect())
+    }
+
+    fn print(node: Node) -> Node {
+        statement(unary(UnaryOp::Print, node))
+    }
+
+    #[test]
+    fn examples_round_trip() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+        let mut count = 0;
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|extension| extension == "lg") {
+                let ast = parse(&fs::read_to_string(&path).unwrap());
+                assert_round_trip(&ast, &ast);
+                count += 1;
+            }
+        }
+        assert!(count > 0);
+    }
+
+    #[test]
+    fn literal_round_trips() {
+        let ast = [print(lit("10")), print(lit("hello")), print(lit(""))];
+        assert_round_trip(&ast, &ast);
+    }
+
+    #[test]
+    fn variable_round_trips() {
+        let ast = [
+            statement(binary(BinaryOp::Make, lit("x"), lit("1"))),
+            print(var("x")),
+        ];
+        assert_round_trip(&ast, &ast);
+    }
+
+    #[test]
+    fn list_round_trips() {
+        let nested = Node::List(vec![lit("2"), lit("\"3")]);
+        let ast = [print(Node::List(vec![
+            lit("1"),
+            nested,
+            Node::List(vec![]),
+        ]))];
+        assert_round_trip(&ast, &ast);
+    }
+
+    #[test]
+    fn leaves_round_trip() {
+        let queries = [
+            Node::Xcor,
+            Node::Ycor,
+            Node::Heading,
+            Node::Pos,
+            Node::Pi,
+            Node::Color,
+            Node::Pensize,
+            Node::Pendownp,
+        ];
+        let commands = [
+            Node::Penup,
+            Node::Pendown,
+            Node::Penpaint,
+            Node::Penerase,
+            Node::Penreverse,
+            Node::Home,
+            Node::Clearscreen,
+            Node::Clean,
+        ];
+        let mut ast: Vec<Node> = queries.into_iter().map(print).collect();
+        ast.extend(commands.into_iter().map(statement));
+        assert_round_trip(&ast, &ast);
+    }
+
+    #[test]
+    fn unary_round_trips() {
+        let ast = [
+            statement(unary(UnaryOp::Forward, lit("10"))),
+            print(unary(
+                UnaryOp::Not,
+                unary(UnaryOp::Emptyp, Node::List(vec![])),
+            )),
+        ];
+        assert_round_trip(&ast, &ast);
+    }
+
+    #[test]
+    fn binary_round_trips() {
+        let sum = binary(
+            BinaryOp::Add,
+            lit("1"),
+            binary(BinaryOp::Mul, var("x"), lit("2")),
+        );
+        let ast = [
+            statement(binary(BinaryOp::Make, lit("x"), sum)),
+            statement(binary(
+                BinaryOp::If,
+                binary(BinaryOp::Gt, var("x"), lit("1")),
+                body(vec![unary(UnaryOp::Right, lit("90"))]),
+            )),
+            statement(binary(
+                BinaryOp::While,
+                binary(BinaryOp::Lt, var("x"), lit("5")),
+                body(vec![binary(BinaryOp::Addassign, lit("x"), lit("1"))]),
+            )),
+            statement(binary(BinaryOp::Setxy, lit("1"), lit("2"))),
+        ];
+        assert_round_trip(&ast, &ast);
+    }
+
+    #[test]
+    fn function_round_trips() {
+        let function = binary(
+            BinaryOp::Func(String::from("sq")),
+            Node::Body(vec![var("a"), var("b")]),
+            body(vec![
+                unary(UnaryOp::Local, lit("c")),
+                unary(UnaryOp::Output, binary(BinaryOp::Mul, var("a"), var("b"))),
+            ]),
+        );
+        let ast = [statement(function)];
+        assert_round_trip(&ast, &ast);
+    }
+
+    #[test]
+    fn ternary_round_trips() {
+        let ast = [statement(Node::TernaryExpr {
+            op: TernaryOp::IfElse,
+            first: Box::new(binary(BinaryOp::Eq, lit("1"), lit("1"))),
+            second: Box::new(body(vec![Node::Penup])),
+            third: Box::new(body(vec![Node::Pendown, unary(UnaryOp::Left, lit("5"))])),
+        })];
+        assert_round_trip(&ast, &ast);
+    }
+
+    #[test]
+    fn for_round_trips() {
+        let ast = [
+            statement(Node::For {
+                var: String::from("i"),
+                start: Box::new(lit("1")),
+                end: Box::new(lit("10")),
+                step: Some(Box::new(lit("2"))),
+                body: Box::new(body(vec![unary(UnaryOp::Print, var("i"))])),
+            }),
+            statement(Node::For {
+                var: String::from("j"),
+                start: Box::new(lit("3")),
+                end: Box::new(lit("1")),
+                step: None,
+                body: Box::new(body(vec![])),
+            }),
+        ];
+        assert_round_trip(&ast, &ast);
+    }
+
+    #[test]
+    fn body_round_trips() {
+        let ast = [statement(binary(
+            BinaryOp::If,
+            lit("TRUE"),
+            body(vec![binary(
+                BinaryOp::If,
+                lit("TRUE"),
+                body(vec![Node::Home]),
+            )]),
+        ))];
+        assert_round_trip(&ast, &ast);
+    }
+
+    #[test]
+    fn statement_round_trips() {
+        let span = Span { line: 7, column: 3 };
+        let ast = [Node::Statement {
+            span,
+            node: Box::new(Node::Home),
+        }];
+        assert_round_trip(&ast, &ast);
+    }
+
+    #[test]
+    fn empty_and_newline_are_not_printed() {
+        let ast = [statement(Node::Home), Node::Empty, Node::Newline];
+        assert_round_trip(&ast, &ast[..1]);
+    }
+
+    #[test]
+    fn caller_round_trips() {
+        let function = binary(
+            BinaryOp::Func(String::from("double")),
+            Node::Body(vec![var("n")]),
+            body(vec![unary(
+                UnaryOp::Output,
+                binary(BinaryOp::Add, var("n"), var("n")),
+            )]),
+        );
+        let call = |arg| Node::Caller {
+            name: String::from("double"),
+            args: Box::new(Node::Body(vec![arg])),
+        };
+        let ast = [
+            statement(function),
+            print(call(call(lit("2")))),
+            statement(call(lit("3"))),
+        ];
+        assert_round_trip(&ast, &ast);
+    }
+}
@@ -0,0 +1,243 @@
+use crate::canvas::CanvasOptions;
+use crate::colour::Colour;
+use crate::interpreter::{Drawing, Line, PenMode};
+use std::io::Write;
+
+///
+/// Struct representing a bitmap image, where pixels stores the red, green
+/// and blue components of each pixel row by row from the top left corner
+///
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    ///
+    /// Creates an image filled with the given colour
+    ///
+    pub fn new(width: u32, height: u32, fill: Colour) -> Self {
+        let pixels = [fill.r, fill.g, fill.b].repeat(width as usize * height as usize);
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Colour {
+        let i = self.index(x, y);
+        Colour::rgb(self.pixels[i], self.pixels[i + 1], self.pixels[i + 2])
+    }
+
+    ///
+    /// Writes the image to the given writer as a PNG file
+    ///
+    pub fn write_png<W: Write>(&self, writer: W) -> Result<(), String> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let res = encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&self.pixels));
+        res.map_err(|e| format!("Could not write PNG: {e}"))
+    }
+
+    ///
+    /// Returns the bytes of the image as a PNG file
+    ///
+    pub fn to_png(&self) -> Result<Vec<u8>, String> {
+        let mut bytes = vec![];
+        self.write_png(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y as usize * self.width as usize + x as usize) * 3
+    }
+
+    ///
+    /// Mixes the pen into the pixel, where coverage is how much
+    /// of the pixel (from 0 to 1) is under the pen
+    ///
+    fn blend(&mut self, x: u32, y: u32, pen: Pen, coverage: f64) {
+        let i = self.index(x, y);
+        for (channel, pixel) in self.pixels[i..i + 3].iter_mut().enumerate() {
+            let target = match pen {
+                Pen::Colour(colour) => [colour.r, colour.g, colour.b][channel],
+                Pen::Reverse => 255 - *pixel,
+            };
+            let mixed = *pixel as f64 + (target as f64 - *pixel as f64) * coverage;
+            *pixel = mixed.round() as u8;
+        }
+    }
+}
+
+///
+/// Enum representing what a line is drawn with
+///
+#[derive(Clone, Copy)]
+enum Pen {
+    Colour(Colour),
+    Reverse,
+}
+
+///
+/// This function is responsible for drawing a drawing onto a bitmap image
+/// The image is filled with the background colour and then every visible
+/// line is drawn in order with anti-aliased edges and round ends
+///
+pub fn rasterise(drawing: &Drawing, options: &CanvasOptions) -> Image {
+    let mut image = Image::new(options.width, options.height, drawing.background);
+    for line in drawing.visible_lines() {
+        let pen = match line.mode {
+            PenMode::Paint => Pen::Colour(line.colour),
+            PenMode::Erase => Pen::Colour(drawing.background),
+            PenMode::Reverse => Pen::Reverse,
+        };
+        draw_line(&mut image, line, pen, options);
+    }
+    image
+}
+
+///
+/// Draws a line by working out how much of each pixel near the line
+/// is within half the pen width of it
+///
+fn draw_line(image: &mut Image, line: &Line, pen: Pen, options: &CanvasOptions) {
+    let (x1, y1) = options.to_canvas(line.from);
+    let (x2, y2) = options.to_canvas(line.to);
+    // thin lines are still drawn one pixel wide
+    let radius = (line.width * options.scale / 2.0).max(0.5);
+
+    // only the pixels within the bounding box of the line can be covered
+    let reach = radius + 1.0;
+    let clamp_x = |v: f64| v.clamp(0.0, image.width as f64) as u32;
+    let clamp_y = |v: f64| v.clamp(0.0, image.height as f64) as u32;
+    let (min_x, max_x) = (clamp_x(x1.min(x2) - reach), clamp_x(x1.max(x2) + reach));
+    let (min_y, max_y) = (clamp_y(y1.min(y2) - reach), clamp_y(y1.max(y2) + reach));
+
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            let distance =
+                distance_to_segment((x as f64 + 0.5, y as f64 + 0.5), (x1, y1), (x2, y2));
+            let coverage = (radius + 0.5 - distance).clamp(0.0, 1.0);
+            if coverage > 0.0 {
+                image.blend(x, y, pen, coverage);
+            }
+        }
+    }
+}
+
+///
+/// Returns the shortest distance from the point to the line segment
+///
+fn distance_to_segment((px, py): (f64, f64), (x1, y1): (f64, f64), (x2, y2): (f64, f64)) -> f64 {
+    let (dx, dy) = (x2 - x1, y2 - y1);
+    let length_squared = dx * dx + dy * dy;
+    let t = if length_squared == 0.0 {
+        0.0
+    } else {
+        (((px - x1) * dx + (py - y1) * dy) / length_squared).clamp(0.0, 1.0)
+    };
+    (px - (x1 + t * dx)).hypot(py - (y1 + t * dy))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::DrawOp;
+
+    fn line(from: (f64, f64), to: (f64, f64), width: f64, mode: PenMode) -> DrawOp {
+        DrawOp::Line(Line {
+            from,
+            to,
+            colour: Colour::rgb(255, 0, 0),
+            width,
+            mode,
+        })
+    }
+
+    ///
+    /// Writes each pixel as its red and green components in hex
+    /// one row per line, e.g. ff00 for red
+    ///
+    fn pixels(image: &Image) -> String {
+        let mut rows = String::new();
+        for y in 0..image.height {
+            let row: Vec<String> = (0..image.width)
+                .map(|x| {
+                    let pixel = image.pixel(x, y);
+                    format!("{:02x}{:02x}", pixel.r, pixel.g)
+                })
+                .collect();
+            rows.push_str(&row.join(" "));
+            rows.push('\n');
+        }
+        rows
+    }
+
+    #[test]
+    fn draws_fixed_drawing() {
+        let drawing = Drawing {
+            background: Colour::rgb(0, 16, 0),
+            ops: vec![
+                line((-3.0, 1.0), (2.0, 1.0), 2.0, PenMode::Paint),
+                line((-3.0, -1.5), (2.0, -2.5), 1.0, PenMode::Paint),
+            ],
+        };
+        let options = CanvasOptions {
+            width: 8,
+            height: 6,
+            origin: None,
+            scale: 1.0,
+        };
+        // the background is filled in, the thick line covers two rows with
+        // partly covered round ends and the thin line is anti-aliased
+        let expected = "\
+0010 0010 0010 0010 0010 0010 0010 0010
+ca03 ff00 ff00 ff00 ff00 ff00 ca03 0010
+ca03 ff00 ff00 ff00 ff00 ff00 ca03 0010
+0010 0010 0010 0010 0010 0010 0010 0010
+8008 e602 b405 8208 500b 1e0e 0010 0010
+0010 1e0e 500b 8208 b405 e602 8008 0010
+";
+        assert_eq!(pixels(&rasterise(&drawing, &options)), expected);
+    }
+
+    #[test]
+    fn erases_and_reverses() {
+        // the centre of each pixel is a whole number of turtle steps from the origin
+        let drawing = Drawing {
+            background: Colour::rgb(0, 16, 0),
+            ops: vec![
+                line((-2.0, 0.0), (2.0, 0.0), 1.0, PenMode::Paint),
+                line((0.0, 0.0), (0.0, 0.0), 1.0, PenMode::Erase),
+                line((1.0, 1.0), (1.0, -1.0), 1.0, PenMode::Reverse),
+            ],
+        };
+        let options = CanvasOptions {
+            width: 5,
+            height: 3,
+            origin: None,
+            scale: 1.0,
+        };
+        let expected = "\
+0010 0010 0010 ffef 0010
+ff00 ff00 0010 00ff ff00
+0010 0010 0010 ffef 0010
+";
+        assert_eq!(pixels(&rasterise(&drawing, &options)), expected);
+    }
+
+    #[test]
+    fn writes_png() {
+        let image = Image::new(3, 2, Colour::rgb(1, 2, 3));
+        let png = image.to_png().unwrap();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        // the IHDR chunk gives the width and height
+        assert_eq!(&png[12..24], b"IHDR\0\0\0\x03\0\0\0\x02");
+    }
+}
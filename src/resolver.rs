@@ -0,0 +1,275 @@
+use crate::ast::*;
+use std::collections::HashSet;
+
+type ResolveResult<T> = Result<T, String>;
+
+///
+/// Enum representing the scoping model used to resolve variables
+///
+/// Dynamic scoping (the default, as in classic Logo) lets a function see the
+/// local variables of every function that is currently calling it, so any
+/// variable that is not local to the function is resolved as Dynamic
+///
+/// Lexical scoping only lets a function see its own local variables and the
+/// global variables, so any variable that is not local is resolved as Global
+///
+/// In both models the local variables of a function are its parameters,
+/// names declared by LOCAL or LOCALMAKE and the variables of FOR loops inside
+/// it. A name only becomes local from the statement that declares it onwards.
+/// Outside of functions every variable is Global
+///
+#[derive(Debug, Clone, Copy, Default)]
+pub enum ScopingModel {
+    #[default]
+    Dynamic,
+    Lexical,
+}
+
+///
+/// This function is responsible for recording the Scope of every Variable
+/// reference in the given AST according to the scoping model
+/// LOCAL and LOCALMAKE are rejected outside of defined functions
+///
+pub fn resolve(ast: &mut [Node], model: ScopingModel) -> ResolveResult<()> {
+    for node in ast.iter_mut() {
+        resolve_node(node, model, None)?;
+    }
+    Ok(())
+}
+
+///
+/// Resolve a single node where locals is the set of names local to the
+/// enclosing defined function, or None at the top level
+///
+fn resolve_node(
+    node: &mut Node,
+    model: ScopingModel,
+    mut locals: Option<&mut HashSet<String>>,
+) -> ResolveResult<()> {
+    match node {
+        Node::Variable(name, scope) => {
+            *scope = match locals {
+                Some(locals) if locals.contains(name.as_str()) => Scope::Local,
+                Some(_) => match model {
+                    ScopingModel::Dynamic => Scope::Dynamic,
+                    ScopingModel::Lexical => Scope::Global,
+                },
+                None => Scope::Global,
+            };
+        }
+        Node::BinaryExpr {
+            op: BinaryOp::Func(_),
+            lhs,
+            rhs,
+        } => {
+            let mut params = HashSet::new();
+            if let Node::Body(args) = lhs.as_mut() {
+                for arg in args.iter_mut() {
+                    match arg {
+                        // "x and :x both declare a parameter named x
+                        Node::Literal(name) | Node::Variable(name, _) => {
+                            params.insert(name.to_string());
+                            if let Node::Variable(_, scope) = arg {
+                                *scope = Scope::Local;
+                            }
+                        }
+                        // other parameters are evaluated by the caller to get a name
+                        _ => resolve_node(arg, model, None)?,
+                    }
+                }
+            }
+            resolve_node(rhs, model, Some(&mut params))?;
+        }
+        Node::UnaryExpr {
+            op: UnaryOp::Local,
+            child,
+        } => match locals {
+            Some(locals) => {
+                declare_locals(child, locals);
+                resolve_node(child, model, Some(locals))?;
+            }
+            None => return Err(String::from("LOCAL can only be used inside a function")),
+        },
+        Node::BinaryExpr {
+            op: BinaryOp::Localmake,
+            lhs,
+            rhs,
+        } => match locals {
+            Some(locals) => {
+                resolve_node(lhs, model, Some(&mut *locals))?;
+                resolve_node(rhs, model, Some(&mut *locals))?;
+                declare_locals(lhs, locals);
+            }
+            None => return Err(String::from("LOCALMAKE can only be used inside a function")),
+        },
+        Node::UnaryExpr { child, .. } => resolve_node(child, model, locals)?,
+        Node::BinaryExpr { lhs, rhs, .. } => {
+            resolve_node(lhs, model, locals.as_deref_mut())?;
+            resolve_node(rhs, model, locals)?;
+        }
+        Node::TernaryExpr {
+            first,
+            second,
+            third,
+            ..
+        } => {
+            resolve_node(first, model, locals.as_deref_mut())?;
+            resolve_node(second, model, locals.as_deref_mut())?;
+            resolve_node(third, model, locals)?;
+        }
+        Node::For {
+            var,
+            start,
+            end,
+            step,
+            body,
+        } => {
+            resolve_node(start, model, locals.as_deref_mut())?;
+            resolve_node(end, model, locals.as_deref_mut())?;
+            if let Some(step) = step {
+                resolve_node(step, model, locals.as_deref_mut())?;
+            }
+            if let Some(locals) = locals.as_deref_mut() {
+                locals.insert(var.to_string());
+            }
+            resolve_node(body, model, locals)?;
+        }
+        Node::Body(nodes) => {
+            for node in nodes.iter_mut() {
+                resolve_node(node, model, locals.as_deref_mut())?;
+            }
+        }
+        Node::Caller { args, .. } => resolve_node(args, model, locals)?,
+        Node::Statement { node, .. } => resolve_node(node, model, locals)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+///
+/// Add the names declared by LOCAL or LOCALMAKE to the set of locals
+/// Names can be given as a quoted word or as a list of words
+///
+fn declare_locals(names: &Node, locals: &mut HashSet<String>) {
+    match names {
+        Node::Literal(name) => {
+            locals.insert(name.to_string());
+        }
+        Node::List(items) => {
+            for item in items {
+                declare_locals(item, locals);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenise_source;
+    use crate::parser::parse_program;
+    use crate::visit::{walk_nodes, Visitor};
+
+    ///
+    /// Struct representing a visitor that keeps the name and
+    /// scope of every variable it reaches, in order
+    ///
+    struct Scopes(Vec<(String, Scope)>);
+
+    impl Visitor for Scopes {
+        fn visit_variable(&mut self, name: &str, scope: Scope) {
+            self.0.push((name.to_string(), scope));
+        }
+    }
+
+    fn resolved(source: &str, model: ScopingModel) -> ResolveResult<Vec<Node>> {
+        let mut ast = parse_program(tokenise_source(source).unwrap()).unwrap();
+        resolve(&mut ast, model)?;
+        Ok(ast)
+    }
+
+    fn scopes(source: &str, model: ScopingModel) -> Vec<(String, Scope)> {
+        let mut scopes = Scopes(vec![]);
+        walk_nodes(&mut scopes, &resolved(source, model).unwrap());
+        scopes.0
+    }
+
+    fn named(scopes: &[(&str, Scope)]) -> Vec<(String, Scope)> {
+        scopes
+            .iter()
+            .map(|(name, scope)| (name.to_string(), *scope))
+            .collect()
+    }
+
+    #[test]
+    fn declared_names_are_local() {
+        let source = "TO f :a\n    PRINT :b\n    LOCAL \"b\n    LOCALMAKE \"c \"1\n    \
+                      FOR [d \"1 \"2] [\n        PRINT :d\n    ]\n    \
+                      PRINT :a\n    PRINT :b\n    PRINT :c\nEND\n";
+        for model in [ScopingModel::Dynamic, ScopingModel::Lexical] {
+            // a name is only local from the statement that declares it onwards
+            let free = match model {
+                ScopingModel::Dynamic => Scope::Dynamic,
+                ScopingModel::Lexical => Scope::Global,
+            };
+            assert_eq!(
+                scopes(source, model),
+                named(&[
+                    ("a", Scope::Local),
+                    ("b", free),
+                    ("d", Scope::Local),
+                    ("a", Scope::Local),
+                    ("b", Scope::Local),
+                    ("c", Scope::Local),
+                ])
+            );
+        }
+    }
+
+    #[test]
+    fn free_variables_depend_on_the_model() {
+        let source = "MAKE \"x \"1\nTO f\n    PRINT :x\nEND\n";
+        assert_eq!(
+            scopes(source, ScopingModel::Dynamic),
+            named(&[("x", Scope::Dynamic)])
+        );
+        assert_eq!(
+            scopes(source, ScopingModel::Lexical),
+            named(&[("x", Scope::Global)])
+        );
+    }
+
+    #[test]
+    fn top_level_is_global() {
+        let source = "MAKE \"x \"1\nPRINT :x\nFOR [i \"1 \"2] [\n    PRINT :i\n]\n\
+                      TO f :x\n    PRINT :x\nEND\nf :x\n";
+        for model in [ScopingModel::Dynamic, ScopingModel::Lexical] {
+            assert_eq!(
+                scopes(source, model),
+                named(&[
+                    ("x", Scope::Global),
+                    ("i", Scope::Global),
+                    ("x", Scope::Local),
+                    ("x", Scope::Local),
+                    ("x", Scope::Global),
+                ])
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_locals_outside_functions() {
+        assert_eq!(
+            resolved("LOCAL \"x\n", ScopingModel::Dynamic),
+            Err(String::from("LOCAL can only be used inside a function"))
+        );
+        assert_eq!(
+            resolved(
+                "IF EQ \"1 \"1 [\n    LOCALMAKE \"x \"1\n]\n",
+                ScopingModel::Lexical
+            ),
+            Err(String::from("LOCALMAKE can only be used inside a function"))
+        );
+    }
+}
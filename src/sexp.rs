@@ -0,0 +1,176 @@
+use crate::ast::*;
+
+/// Lists longer than this are split over several lines
+const MAX_WIDTH: usize = 80;
+
+///
+/// Enum representing an S-expression, which is either a single
+/// word or a list of S-expressions written in brackets
+///
+enum Sexp {
+    Atom(String),
+    List(Vec<Sexp>),
+}
+
+///
+/// This function is responsible for writing an AST as S-expressions
+/// Every operation is written as a list starting with its keyword, e.g.
+/// FORWARD + :x "10 is written as (FORWARD (+ :x "10)), and codeblocks are
+/// written as a list of their statements. Literals, variables and data lists
+/// are written as they are in logo code
+///
+/// Note that each statement is written on its own line, and any list that
+/// does not fit within 80 columns has its items indented on their own lines
+///
+pub fn to_sexp(ast: &[Node]) -> String {
+    let mut out = String::new();
+    for node in ast {
+        write_sexp(&sexp(node), 0, &mut out);
+        out.push('\n');
+    }
+    out
+}
+
+fn sexp(node: &Node) -> Sexp {
+    let atom = |word: &str| Sexp::Atom(word.to_string());
+    match node {
+        Node::Literal(word) => Sexp::Atom(format!("\"{word}")),
+        Node::Variable(name, _) => Sexp::Atom(format!(":{name}")),
+        Node::List(_) => Sexp::Atom(data_list(node)),
+        Node::UnaryExpr { op, child } => Sexp::List(vec![atom(op.keyword()), sexp(child)]),
+        Node::BinaryExpr {
+            op: BinaryOp::Func(name),
+            lhs,
+            rhs,
+        } => Sexp::List(vec![atom("TO"), atom(name), sexp(lhs), sexp(rhs)]),
+        Node::BinaryExpr { op, lhs, rhs } => {
+            Sexp::List(vec![atom(op.keyword()), sexp(lhs), sexp(rhs)])
+        }
+        Node::TernaryExpr {
+            op,
+            first,
+            second,
+            third,
+        } => Sexp::List(vec![
+            atom(op.keyword()),
+            sexp(first),
+            sexp(second),
+            sexp(third),
+        ]),
+        Node::For {
+            var,
+            start,
+            end,
+            step,
+            body,
+        } => {
+            let mut items = vec![atom("FOR"), atom(var), sexp(start), sexp(end)];
+            if let Some(step) = step {
+                items.push(sexp(step));
+            }
+            items.push(sexp(body));
+            Sexp::List(items)
+        }
+        Node::Body(nodes) => Sexp::List(nodes.iter().map(sexp).collect()),
+        Node::Statement { node, .. } => sexp(node),
+        Node::Empty | Node::Newline => Sexp::List(vec![]),
+        Node::Caller { name, args } => {
+            let mut items = vec![atom(name)];
+            if let Node::Body(args) = args.as_ref() {
+                items.extend(args.iter().map(sexp));
+            }
+            Sexp::List(items)
+        }
+        node => atom(node.keyword().unwrap_or_default()),
+    }
+}
+
+///
+/// Writes a data list as it is written in logo code, e.g. [1 [2 3]]
+///
+pub(crate) fn data_list(node: &Node) -> String {
+    match node {
+        Node::List(items) => {
+            let items: Vec<String> = items.iter().map(data_list).collect();
+            format!("[{}]", items.join(" "))
+        }
+        Node::Literal(word) => word.to_string(),
+        _ => String::new(),
+    }
+}
+
+///
+/// Returns the S-expression written on a single line
+///
+fn flat(sexp: &Sexp) -> String {
+    match sexp {
+        Sexp::Atom(word) => word.to_string(),
+        Sexp::List(items) => {
+            let items: Vec<String> = items.iter().map(flat).collect();
+            format!("({})", items.join(" "))
+        }
+    }
+}
+
+fn write_sexp(sexp: &Sexp, indent: usize, out: &mut String) {
+    let line = flat(sexp);
+    let items = match sexp {
+        Sexp::List(items) if indent + line.chars().count() > MAX_WIDTH && items.len() > 1 => items,
+        _ => {
+            out.push_str(&line);
+            return;
+        }
+    };
+
+    // the first item stays next to the bracket, where the rest of the items
+    // line up under the keyword of an operation, or under the first statement
+    // of a codeblock
+    let item_indent = match items[0] {
+        Sexp::Atom(_) => indent + 2,
+        Sexp::List(_) => indent + 1,
+    };
+    out.push('(');
+    write_sexp(&items[0], indent + 1, out);
+    for item in &items[1..] {
+        out.push('\n');
+        out.push_str(&" ".repeat(item_indent));
+        write_sexp(item, item_indent, out);
+    }
+    out.push(')');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenise_source;
+    use crate::parser::parse_program;
+
+    fn parse(source: &str) -> Vec<Node> {
+        parse_program(tokenise_source(source).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn writes_nested_expressions() {
+        let source = "FORWARD + :x * \"2 - \"10 :y\nFOR [i \"1 \"9 \"2] [\n    PRINT :i\n]\n";
+        let expected = "(FORWARD (+ :x (* \"2 (- \"10 :y))))\n(FOR i \"1 \"9 \"2 ((PRINT :i)))\n";
+        assert_eq!(to_sexp(&parse(source)), expected);
+    }
+
+    #[test]
+    fn wraps_lists_past_max_width() {
+        let source = "MAKE \"total SENTENCE LIST FIRST [alpha beta gamma] \
+                      LAST [delta epsilon zeta] BUTFIRST [eta theta iota kappa]\n";
+        let expected = "\
+(MAKE
+  \"total
+  (SENTENCE
+    (LIST (FIRST [alpha beta gamma]) (LAST [delta epsilon zeta]))
+    (BUTFIRST [eta theta iota kappa])))
+";
+        let written = to_sexp(&parse(source));
+        assert_eq!(written, expected);
+        assert!(written
+            .lines()
+            .all(|line| line.chars().count() <= MAX_WIDTH));
+    }
+}
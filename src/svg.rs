@@ -0,0 +1,107 @@
+use crate::canvas::CanvasOptions;
+use crate::interpreter::{Drawing, PenMode};
+use std::fmt::Write;
+
+///
+/// This function is responsible for turning a drawing into an SVG document
+/// Every visible line is written in the order it was drawn, and numbers are
+/// rounded to two decimal places so the same drawing always gives the same SVG
+///
+pub fn render_svg(drawing: &Drawing, options: &CanvasOptions) -> String {
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{0}" height="{1}" viewBox="0 0 {0} {1}">"#,
+        options.width, options.height
+    );
+    let _ = writeln!(
+        svg,
+        r#"<rect width="100%" height="100%" fill="{}"/>"#,
+        drawing.background.to_hex()
+    );
+
+    for line in drawing.visible_lines() {
+        let (x1, y1) = options.to_canvas(line.from);
+        let (x2, y2) = options.to_canvas(line.to);
+        let (stroke, style) = match line.mode {
+            PenMode::Paint => (line.colour.to_hex(), ""),
+            PenMode::Erase => (drawing.background.to_hex(), ""),
+            // drawing white with a difference blend inverts what is underneath
+            PenMode::Reverse => (
+                String::from("#ffffff"),
+                r#" style="mix-blend-mode:difference""#,
+            ),
+        };
+        let _ = writeln!(
+            svg,
+            r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}" stroke-width="{}" stroke-linecap="round"{}/>"#,
+            format_number(x1),
+            format_number(y1),
+            format_number(x2),
+            format_number(y2),
+            stroke,
+            format_number(line.width * options.scale),
+            style
+        );
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+///
+/// Formats a number with at most two decimal places and no trailing zeros
+///
+fn format_number(n: f64) -> String {
+    let rounded = format!("{:.2}", n);
+    let trimmed = rounded.trim_end_matches('0').trim_end_matches('.');
+    match trimmed {
+        "-0" | "" => String::from("0"),
+        _ => trimmed.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour::Colour;
+    use crate::interpreter::{DrawOp, Line};
+
+    fn line(from: (f64, f64), to: (f64, f64), mode: PenMode) -> DrawOp {
+        DrawOp::Line(Line {
+            from,
+            to,
+            colour: Colour::rgb(255, 0, 0),
+            width: 1.5,
+            mode,
+        })
+    }
+
+    #[test]
+    fn renders_fixed_drawing() {
+        let drawing = Drawing {
+            background: Colour::rgb(0, 0, 0),
+            ops: vec![
+                line((0.0, 0.0), (50.0, 50.0), PenMode::Paint),
+                DrawOp::Clear,
+                line((0.0, 0.0), (0.0, 100.0), PenMode::Paint),
+                line((0.0, 100.0), (33.333, 100.0), PenMode::Erase),
+                line((-10.0, -0.001), (10.0, 0.0), PenMode::Reverse),
+            ],
+        };
+        let options = CanvasOptions {
+            width: 200,
+            height: 300,
+            origin: None,
+            scale: 2.0,
+        };
+        let expected = r##"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="300" viewBox="0 0 200 300">
+<rect width="100%" height="100%" fill="#000000"/>
+<line x1="100" y1="150" x2="100" y2="-50" stroke="#ff0000" stroke-width="3" stroke-linecap="round"/>
+<line x1="100" y1="-50" x2="166.67" y2="-50" stroke="#000000" stroke-width="3" stroke-linecap="round"/>
+<line x1="80" y1="150" x2="120" y2="150" stroke="#ffffff" stroke-width="3" stroke-linecap="round" style="mix-blend-mode:difference"/>
+</svg>
+"##;
+        assert_eq!(render_svg(&drawing, &options), expected);
+    }
+}
@@ -0,0 +1,453 @@
+use crate::ast::*;
+
+///
+/// This trait is responsible for walking an AST without changing it
+/// visit_node is called for every node, which calls the hook for its variant
+/// By default each hook visits the children of the node in the order they
+/// are written in logo code, so an analysis only overrides the hooks for
+/// the nodes it cares about. A hook that still wants the children visited
+/// calls the walk function of the same name, e.g. walk_unary
+///
+/// Note that walk_node matches every variant of Node without a catch-all,
+/// so adding a variant to ast.rs does not build until it is walked here
+///
+pub trait Visitor {
+    fn visit_node(&mut self, node: &Node) {
+        walk_node(self, node);
+    }
+
+    fn visit_literal(&mut self, _word: &str) {}
+
+    fn visit_variable(&mut self, _name: &str, _scope: Scope) {}
+
+    fn visit_list(&mut self, items: &[Node]) {
+        walk_nodes(self, items);
+    }
+
+    ///
+    /// Called for the nodes that are a single keyword, such as PENUP or XCOR,
+    /// and for Empty and Newline
+    ///
+    fn visit_leaf(&mut self, _node: &Node) {}
+
+    fn visit_unary(&mut self, op: &UnaryOp, child: &Node) {
+        walk_unary(self, op, child);
+    }
+
+    fn visit_binary(&mut self, op: &BinaryOp, lhs: &Node, rhs: &Node) {
+        walk_binary(self, op, lhs, rhs);
+    }
+
+    ///
+    /// Called for TO instead of visit_binary, where params is the
+    /// Body of the parameters and body is the Body of the function
+    ///
+    fn visit_function(&mut self, name: &str, params: &Node, body: &Node) {
+        walk_function(self, name, params, body);
+    }
+
+    fn visit_ternary(&mut self, op: &TernaryOp, first: &Node, second: &Node, third: &Node) {
+        walk_ternary(self, op, first, second, third);
+    }
+
+    fn visit_for(&mut self, var: &str, start: &Node, end: &Node, step: Option<&Node>, body: &Node) {
+        walk_for(self, var, start, end, step, body);
+    }
+
+    fn visit_body(&mut self, nodes: &[Node]) {
+        walk_nodes(self, nodes);
+    }
+
+    fn visit_statement(&mut self, span: Span, node: &Node) {
+        walk_statement(self, span, node);
+    }
+
+    fn visit_caller(&mut self, name: &str, args: &Node) {
+        walk_caller(self, name, args);
+    }
+}
+
+///
+/// Calls the hook of the visitor for the variant of the node
+///
+pub fn walk_node<V: Visitor + ?Sized>(visitor: &mut V, node: &Node) {
+    match node {
+        Node::Literal(word) => visitor.visit_literal(word),
+        Node::Variable(name, scope) => visitor.visit_variable(name, *scope),
+        Node::List(items) => visitor.visit_list(items),
+        Node::Xcor
+        | Node::Ycor
+        | Node::Heading
+        | Node::Pos
+        | Node::Pi
+        | Node::Color
+        | Node::Pensize
+        | Node::Pendownp
+        | Node::Penup
+        | Node::Pendown
+        | Node::Penpaint
+        | Node::Penerase
+        | Node::Penreverse
+        | Node::Stop
+        | Node::Home
+        | Node::Clearscreen
+        | Node::Clean
+        | Node::Empty
+        | Node::Newline => visitor.visit_leaf(node),
+        Node::UnaryExpr { op, child } => visitor.visit_unary(op, child),
+        Node::BinaryExpr {
+            op: BinaryOp::Func(name),
+            lhs,
+            rhs,
+        } => visitor.visit_function(name, lhs, rhs),
+        Node::BinaryExpr { op, lhs, rhs } => visitor.visit_binary(op, lhs, rhs),
+        Node::TernaryExpr {
+            op,
+            first,
+            second,
+            third,
+        } => visitor.visit_ternary(op, first, second, third),
+        Node::For {
+            var,
+            start,
+            end,
+            step,
+            body,
+        } => visitor.visit_for(var, start, end, step.as_deref(), body),
+        Node::Body(nodes) => visitor.visit_body(nodes),
+        Node::Statement { span, node } => visitor.visit_statement(*span, node),
+        Node::Caller { name, args } => visitor.visit_caller(name, args),
+    }
+}
+
+pub fn walk_nodes<V: Visitor + ?Sized>(visitor: &mut V, nodes: &[Node]) {
+    for node in nodes {
+        visitor.visit_node(node);
+    }
+}
+
+pub fn walk_unary<V: Visitor + ?Sized>(visitor: &mut V, _op: &UnaryOp, child: &Node) {
+    visitor.visit_node(child);
+}
+
+pub fn walk_binary<V: Visitor + ?Sized>(visitor: &mut V, _op: &BinaryOp, lhs: &Node, rhs: &Node) {
+    visitor.visit_node(lhs);
+    visitor.visit_node(rhs);
+}
+
+pub fn walk_function<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    _name: &str,
+    params: &Node,
+    body: &Node,
+) {
+    visitor.visit_node(params);
+    visitor.visit_node(body);
+}
+
+pub fn walk_ternary<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    _op: &TernaryOp,
+    first: &Node,
+    second: &Node,
+    third: &Node,
+) {
+    visitor.visit_node(first);
+    visitor.visit_node(second);
+    visitor.visit_node(third);
+}
+
+pub fn walk_for<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    _var: &str,
+    start: &Node,
+    end: &Node,
+    step: Option<&Node>,
+    body: &Node,
+) {
+    visitor.visit_node(start);
+    visitor.visit_node(end);
+    if let Some(step) = step {
+        visitor.visit_node(step);
+    }
+    visitor.visit_node(body);
+}
+
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, _span: Span, node: &Node) {
+    visitor.visit_node(node);
+}
+
+pub fn walk_caller<V: Visitor + ?Sized>(visitor: &mut V, _name: &str, args: &Node) {
+    visitor.visit_node(args);
+}
+
+///
+/// This trait is responsible for changing an AST in place
+/// It has the same hooks as Visitor with mutable access to each part of
+/// the node, and visit_node_mut can replace a node outright. The walk_*_mut
+/// functions visit the children the same way as the walk functions of Visitor
+///
+pub trait VisitorMut {
+    fn visit_node_mut(&mut self, node: &mut Node) {
+        walk_node_mut(self, node);
+    }
+
+    fn visit_literal_mut(&mut self, _word: &mut String) {}
+
+    fn visit_variable_mut(&mut self, _name: &mut String, _scope: &mut Scope) {}
+
+    fn visit_list_mut(&mut self, items: &mut Vec<Node>) {
+        walk_nodes_mut(self, items);
+    }
+
+    fn visit_leaf_mut(&mut self, _node: &mut Node) {}
+
+    fn visit_unary_mut(&mut self, op: &mut UnaryOp, child: &mut Node) {
+        walk_unary_mut(self, op, child);
+    }
+
+    fn visit_binary_mut(&mut self, op: &mut BinaryOp, lhs: &mut Node, rhs: &mut Node) {
+        walk_binary_mut(self, op, lhs, rhs);
+    }
+
+    fn visit_function_mut(&mut self, name: &mut String, params: &mut Node, body: &mut Node) {
+        walk_function_mut(self, name, params, body);
+    }
+
+    fn visit_ternary_mut(
+        &mut self,
+        op: &mut TernaryOp,
+        first: &mut Node,
+        second: &mut Node,
+        third: &mut Node,
+    ) {
+        walk_ternary_mut(self, op, first, second, third);
+    }
+
+    fn visit_for_mut(
+        &mut self,
+        var: &mut String,
+        start: &mut Node,
+        end: &mut Node,
+        step: Option<&mut Node>,
+        body: &mut Node,
+    ) {
+        walk_for_mut(self, var, start, end, step, body);
+    }
+
+    fn visit_body_mut(&mut self, nodes: &mut Vec<Node>) {
+        walk_nodes_mut(self, nodes);
+    }
+
+    fn visit_statement_mut(&mut self, span: &mut Span, node: &mut Node) {
+        walk_statement_mut(self, span, node);
+    }
+
+    fn visit_caller_mut(&mut self, name: &mut String, args: &mut Node) {
+        walk_caller_mut(self, name, args);
+    }
+}
+
+///
+/// Calls the hook of the visitor for the variant of the node
+///
+pub fn walk_node_mut<V: VisitorMut + ?Sized>(visitor: &mut V, node: &mut Node) {
+    match node {
+        Node::Literal(word) => visitor.visit_literal_mut(word),
+        Node::Variable(name, scope) => visitor.visit_variable_mut(name, scope),
+        Node::List(items) => visitor.visit_list_mut(items),
+        Node::Xcor
+        | Node::Ycor
+        | Node::Heading
+        | Node::Pos
+        | Node::Pi
+        | Node::Color
+        | Node::Pensize
+        | Node::Pendownp
+        | Node::Penup
+        | Node::Pendown
+        | Node::Penpaint
+        | Node::Penerase
+        | Node::Penreverse
+        | Node::Stop
+        | Node::Home
+        | Node::Clearscreen
+        | Node::Clean
+        | Node::Empty
+        | Node::Newline => visitor.visit_leaf_mut(node),
+        Node::UnaryExpr { op, child } => visitor.visit_unary_mut(op, child),
+        Node::BinaryExpr {
+            op: BinaryOp::Func(name),
+            lhs,
+            rhs,
+        } => visitor.visit_function_mut(name, lhs, rhs),
+        Node::BinaryExpr { op, lhs, rhs } => visitor.visit_binary_mut(op, lhs, rhs),
+        Node::TernaryExpr {
+            op,
+            first,
+            second,
+            third,
+        } => visitor.visit_ternary_mut(op, first, second, third),
+        Node::For {
+            var,
+            start,
+            end,
+            step,
+            body,
+        } => visitor.visit_for_mut(var, start, end, step.as_deref_mut(), body),
+        Node::Body(nodes) => visitor.visit_body_mut(nodes),
+        Node::Statement { span, node } => visitor.visit_statement_mut(span, node),
+        Node::Caller { name, args } => visitor.visit_caller_mut(name, args),
+    }
+}
+
+pub fn walk_nodes_mut<V: VisitorMut + ?Sized>(visitor: &mut V, nodes: &mut [Node]) {
+    for node in nodes {
+        visitor.visit_node_mut(node);
+    }
+}
+
+pub fn walk_unary_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    _op: &mut UnaryOp,
+    child: &mut Node,
+) {
+    visitor.visit_node_mut(child);
+}
+
+pub fn walk_binary_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    _op: &mut BinaryOp,
+    lhs: &mut Node,
+    rhs: &mut Node,
+) {
+    visitor.visit_node_mut(lhs);
+    visitor.visit_node_mut(rhs);
+}
+
+pub fn walk_function_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    _name: &mut String,
+    params: &mut Node,
+    body: &mut Node,
+) {
+    visitor.visit_node_mut(params);
+    visitor.visit_node_mut(body);
+}
+
+pub fn walk_ternary_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    _op: &mut TernaryOp,
+    first: &mut Node,
+    second: &mut Node,
+    third: &mut Node,
+) {
+    visitor.visit_node_mut(first);
+    visitor.visit_node_mut(second);
+    visitor.visit_node_mut(third);
+}
+
+pub fn walk_for_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    _var: &mut String,
+    start: &mut Node,
+    end: &mut Node,
+    step: Option<&mut Node>,
+    body: &mut Node,
+) {
+    visitor.visit_node_mut(start);
+    visitor.visit_node_mut(end);
+    if let Some(step) = step {
+        visitor.visit_node_mut(step);
+    }
+    visitor.visit_node_mut(body);
+}
+
+pub fn walk_statement_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    _span: &mut Span,
+    node: &mut Node,
+) {
+    visitor.visit_node_mut(node);
+}
+
+pub fn walk_caller_mut<V: VisitorMut + ?Sized>(
+    visitor: &mut V,
+    _name: &mut String,
+    args: &mut Node,
+) {
+    visitor.visit_node_mut(args);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenise_source;
+    use crate::parser::parse_program;
+
+    ///
+    /// Struct representing a visitor that keeps the words of the literals
+    /// and the names of the variables it reaches, in order
+    ///
+    struct Words(Vec<String>);
+
+    impl Visitor for Words {
+        fn visit_literal(&mut self, word: &str) {
+            self.0.push(word.to_string());
+        }
+
+        fn visit_variable(&mut self, name: &str, _scope: Scope) {
+            self.0.push(format!(":{name}"));
+        }
+    }
+
+    #[test]
+    fn default_walk_reaches_every_child() {
+        let source = "TO f :a\n    OUTPUT + :a \"1\nEND\n\
+                      FOR [i \"2 \"3 \"4] [\n    IFELSE EQ :i \"5 [\n        PRINT f \"6\n    ] [\n        SETPOS [7 8]\n    ]\n]\n";
+        let ast = parse_program(tokenise_source(source).unwrap()).unwrap();
+        let mut words = Words(vec![]);
+        walk_nodes(&mut words, &ast);
+        assert_eq!(
+            words.0,
+            [":a", ":a", "1", "2", "3", "4", ":i", "5", "6", "7", "8"]
+        );
+    }
+
+    #[test]
+    fn hooks_that_do_not_walk_skip_the_children() {
+        struct TopLevel(Words);
+
+        impl Visitor for TopLevel {
+            fn visit_literal(&mut self, word: &str) {
+                self.0.visit_literal(word);
+            }
+
+            fn visit_function(&mut self, _name: &str, _params: &Node, _body: &Node) {}
+        }
+
+        let source = "TO f :a\n    FORWARD \"1\nEND\nf \"2\n";
+        let ast = parse_program(tokenise_source(source).unwrap()).unwrap();
+        let mut top_level = TopLevel(Words(vec![]));
+        walk_nodes(&mut top_level, &ast);
+        assert_eq!(top_level.0 .0, ["2"]);
+    }
+
+    #[test]
+    fn mutable_walk_reaches_every_child() {
+        struct Doubler;
+
+        impl VisitorMut for Doubler {
+            fn visit_literal_mut(&mut self, word: &mut String) {
+                *word = word.repeat(2);
+            }
+        }
+
+        let source = "FOR [i \"1 \"2 \"3] [\n    FORWARD \"4\n]\n";
+        let mut ast = parse_program(tokenise_source(source).unwrap()).unwrap();
+        walk_nodes_mut(&mut Doubler, &mut ast);
+        let mut words = Words(vec![]);
+        walk_nodes(&mut words, &ast);
+        assert_eq!(words.0, ["11", "22", "33", "44"]);
+    }
+}
@@ -0,0 +1,500 @@
+use crate::ast::Span;
+use crate::bytecode::{Chunk, Instruction, Param, Program};
+use crate::interpreter::{for_finished, Drawing, Limits, RuntimeError, State, Turtle, Value};
+use crate::output::OutputChannel;
+use crate::resolver::ScopingModel;
+use std::collections::HashMap;
+
+type RuntimeResult<T> = Result<T, RuntimeError>;
+
+///
+/// Struct representing a function call that is running, storing
+/// where to carry on in the caller once the function returns
+/// and the position of the statement that made the call
+///
+struct CallFrame<'p> {
+    name: &'p str,
+    chunk: &'p Chunk,
+    ip: usize,
+    span: Option<Span>,
+    stack_base: usize,
+    loops_base: usize,
+    output: bool,
+}
+
+///
+/// Struct representing a FOR loop that is running, where i counts
+/// the values the loop variable has been given so far
+///
+struct ForLoop {
+    start: f64,
+    end: f64,
+    step: f64,
+    i: f64,
+}
+
+///
+/// This struct is responsible for running a compiled program with a
+/// value stack and call frames, giving the same output and drawing as
+/// running the AST with the Interpreter
+///
+pub struct Vm<'a> {
+    state: State<'a>,
+}
+
+impl<'a> Vm<'a> {
+    ///
+    /// Creates a VM that sends PRINT, SHOW and TYPE to output
+    ///
+    pub fn new(output: &'a mut dyn OutputChannel) -> Self {
+        Vm {
+            state: State::new(output),
+        }
+    }
+
+    pub fn with_scoping(mut self, scoping: ScopingModel) -> Self {
+        self.state.scoping = scoping;
+        self
+    }
+
+    ///
+    /// Sets the seed used by RANDOM, which otherwise
+    /// gives the same numbers on every run
+    ///
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.state.set_seed(seed);
+        self
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.state.limits = limits;
+        self
+    }
+
+    pub fn turtle(&self) -> &Turtle {
+        &self.state.turtle
+    }
+
+    pub fn drawing(&self) -> &Drawing {
+        &self.state.drawing
+    }
+
+    pub fn into_drawing(self) -> Drawing {
+        self.state.drawing
+    }
+
+    ///
+    /// Runs the main chunk of the program until it stops
+    /// Functions defined by the program are only known during the run
+    ///
+    pub fn run(&mut self, program: &Program) -> RuntimeResult<()> {
+        self.state.start();
+        let mut functions = HashMap::new();
+        self.execute(program, &program.main, &mut functions)
+            .map(|_| ())
+    }
+
+    ///
+    /// Runs a chunk until it returns, giving the value it passed to OUTPUT
+    /// Calls to defined functions are run in the same loop using call frames
+    ///
+    fn execute<'p>(
+        &mut self,
+        program: &'p Program,
+        chunk: &'p Chunk,
+        functions: &mut HashMap<&'p str, usize>,
+    ) -> RuntimeResult<Option<Value>> {
+        let mut calls: Vec<CallFrame> = vec![];
+        let mut stack: Vec<Value> = vec![];
+        let mut loops: Vec<ForLoop> = vec![];
+        let mut chunk = chunk;
+        let mut ip = 0;
+
+        loop {
+            let instruction = &chunk.code[ip];
+            // instructions without a position keep the position of the caller
+            if let Some(span) = chunk.spans[ip] {
+                self.state.span = Some(span);
+            }
+            ip += 1;
+            self.state.step()?;
+
+            match instruction {
+                Instruction::Constant(index) => stack.push(program.constants[*index].clone()),
+                Instruction::Variable(index, scope) => {
+                    stack.push(self.state.lookup(program.name(*index), *scope)?)
+                }
+                Instruction::Query(node) => match self.state.query(node) {
+                    Some(value) => stack.push(value),
+                    None => return Err(self.state.error(String::from("Not enough inputs"))),
+                },
+                Instruction::Leaf(node) => {
+                    self.state.exec_leaf(node)?;
+                }
+                Instruction::Unary(op) => {
+                    let value = self.pop(&mut stack)?;
+                    stack.push(self.state.unary(op, value)?);
+                }
+                Instruction::Binary(op) => {
+                    let b = self.pop(&mut stack)?;
+                    let a = self.pop(&mut stack)?;
+                    stack.push(self.state.binary(op, a, b)?);
+                }
+                Instruction::Command(op) => {
+                    let value = self.pop(&mut stack)?;
+                    self.state.command(op, value)?;
+                }
+                Instruction::BinaryCommand(op) => {
+                    let b = self.pop(&mut stack)?;
+                    let a = self.pop(&mut stack)?;
+                    self.state.binary_command(op, a, b)?;
+                }
+                Instruction::Unused => {
+                    let value = self.pop(&mut stack)?;
+                    return Err(self.state.unused(value));
+                }
+                Instruction::Error(index) => {
+                    return Err(self.state.error(program.name(*index).to_string()))
+                }
+                Instruction::Jump(target) => ip = *target,
+                Instruction::JumpIfFalse(target) => {
+                    let value = self.pop(&mut stack)?;
+                    if !self.state.to_bool(value)? {
+                        ip = *target;
+                    }
+                }
+                Instruction::Define(index) => {
+                    let function = &program.functions[*index];
+                    functions.insert(&function.name, *index);
+                }
+                Instruction::Call { name, argc, output } => {
+                    let name = program.name(*name);
+                    let function = match functions.get(name) {
+                        Some(index) => &program.functions[*index],
+                        None => {
+                            return Err(self.state.error(format!("I don't know how to {name}")))
+                        }
+                    };
+
+                    if stack.len() < *argc {
+                        return Err(self.state.error(String::from("Not enough inputs")));
+                    }
+                    let args = stack.split_off(stack.len() - argc);
+                    let mut frame = HashMap::new();
+                    for (param, arg) in function.params.iter().zip(args) {
+                        let name = match param {
+                            Param::Name(name) => name.to_string(),
+                            Param::Computed(param) => {
+                                match self.execute(program, param, functions)? {
+                                    Some(value) => self.state.to_name(value)?,
+                                    None => {
+                                        return Err(self.state.error(format!(
+                                            "A parameter of {name} did not output a name"
+                                        )))
+                                    }
+                                }
+                            }
+                        };
+                        frame.insert(name, Some(arg));
+                    }
+                    self.state.check_depth()?;
+                    self.state.frames.push(frame);
+
+                    calls.push(CallFrame {
+                        name,
+                        chunk,
+                        ip,
+                        span: self.state.span,
+                        stack_base: stack.len(),
+                        loops_base: loops.len(),
+                        output: *output,
+                    });
+                    chunk = &function.chunk;
+                    ip = 0;
+                }
+                Instruction::Output | Instruction::Stop => {
+                    let value = match instruction {
+                        Instruction::Output => Some(self.pop(&mut stack)?),
+                        _ => None,
+                    };
+                    let call = match calls.pop() {
+                        Some(call) => call,
+                        None => return Ok(value),
+                    };
+
+                    self.state.frames.pop();
+                    stack.truncate(call.stack_base);
+                    loops.truncate(call.loops_base);
+                    chunk = call.chunk;
+                    ip = call.ip;
+                    self.state.span = call.span;
+
+                    // any value passed to OUTPUT is not used by a call run as a command
+                    match (call.output, value) {
+                        (true, Some(value)) => stack.push(value),
+                        (true, None) => {
+                            return Err(self
+                                .state
+                                .error(format!("{} did not output a value", call.name)))
+                        }
+                        (false, _) => {}
+                    }
+                }
+                Instruction::ForStart { step } => {
+                    let step = match step {
+                        true => Some(self.pop(&mut stack)?),
+                        false => None,
+                    };
+                    let end = self.pop(&mut stack)?;
+                    let start = self.pop(&mut stack)?;
+                    let start = self.state.to_number(start)?;
+                    let end = self.state.to_number(end)?;
+                    let step = match step {
+                        Some(step) => Some(self.state.to_number(step)?),
+                        None => None,
+                    };
+                    let step = self.state.for_step(start, end, step)?;
+                    loops.push(ForLoop {
+                        start,
+                        end,
+                        step,
+                        i: 0.0,
+                    });
+                }
+                Instruction::ForNext { var, exit } => {
+                    let current = match loops.last_mut() {
+                        Some(current) => current,
+                        None => {
+                            return Err(self.state.error(String::from("No FOR loop is running")))
+                        }
+                    };
+                    // each value is worked out from the start so that steps do not add up errors
+                    let value = current.start + current.i * current.step;
+                    if for_finished(value, current.end, current.step) {
+                        loops.pop();
+                        ip = *exit;
+                    } else {
+                        current.i += 1.0;
+                        self.state.set_for_var(program.name(*var), value);
+                    }
+                }
+            }
+        }
+    }
+
+    ///
+    /// Pops the value on top of the stack, which the compiler makes sure
+    /// is always there, although bytecode made by hand may not be
+    ///
+    fn pop(&self, stack: &mut Vec<Value>) -> RuntimeResult<Value> {
+        match stack.pop() {
+            Some(value) => Ok(value),
+            None => Err(self.state.error(String::from("Not enough inputs"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Node;
+    use crate::compiler::compile;
+    use crate::interpreter::{ErrorKind, Interpreter};
+    use crate::lexer::tokenise_source;
+    use crate::output::CapturedOutput;
+    use crate::parser::parse_program;
+    use crate::resolver::resolve;
+    use std::fs;
+    use std::path::Path;
+    use std::time::Duration;
+
+    type RunResult = Result<(Vec<String>, Drawing), String>;
+
+    fn parse(source: &str, model: ScopingModel) -> Vec<Node> {
+        let mut ast = parse_program(tokenise_source(source).unwrap()).unwrap();
+        resolve(&mut ast, model).unwrap();
+        ast
+    }
+
+    fn interpret(ast: &[Node], model: ScopingModel) -> RunResult {
+        let mut output = CapturedOutput::new();
+        let mut interpreter = Interpreter::new(&mut output).with_scoping(model);
+        let res = interpreter.run(ast).map_err(|e| e.to_string());
+        let drawing = interpreter.into_drawing();
+        res.map(|()| (output.into_lines(), drawing))
+    }
+
+    fn run_vm(ast: &[Node], model: ScopingModel) -> RunResult {
+        let program = compile(ast).unwrap();
+        let mut output = CapturedOutput::new();
+        let mut vm = Vm::new(&mut output).with_scoping(model);
+        let res = vm.run(&program).map_err(|e| e.to_string());
+        let drawing = vm.into_drawing();
+        res.map(|()| (output.into_lines(), drawing))
+    }
+
+    fn assert_same(name: &str, source: &str) {
+        for model in [ScopingModel::Dynamic, ScopingModel::Lexical] {
+            let ast = parse(source, model);
+            assert_eq!(
+                interpret(&ast, model),
+                run_vm(&ast, model),
+                "{name} differs with {model:?} scoping"
+            );
+        }
+    }
+
+    #[test]
+    fn examples_match_interpreter() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+        let mut count = 0;
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|extension| extension == "lg") {
+                let source = fs::read_to_string(&path).unwrap();
+                assert_same(&path.display().to_string(), &source);
+                count += 1;
+            }
+        }
+        assert!(count > 0);
+    }
+
+    #[test]
+    fn arguments_run_before_computed_parameters() {
+        let source = "TO name\n    PRINT \"param\n    OUTPUT \"x\nEND\n\
+                      TO val\n    PRINT \"arg\n    OUTPUT \"1\nEND\n\
+                      TO f name\n    PRINT :x\nEND\n\
+                      f val\n";
+        assert_same("computed parameters", source);
+        let (lines, _) =
+            interpret(&parse(source, ScopingModel::Dynamic), ScopingModel::Dynamic).unwrap();
+        assert_eq!(lines, ["arg", "param", "1"]);
+    }
+
+    ///
+    /// Runs bytecode made by hand, giving the message of the error it stops with
+    ///
+    fn run_code(code: Vec<Instruction>) -> String {
+        let program = Program {
+            constants: vec![],
+            main: Chunk {
+                spans: vec![None; code.len()],
+                code,
+            },
+            functions: vec![],
+        };
+        let mut output = CapturedOutput::new();
+        let mut vm = Vm::new(&mut output);
+        vm.run(&program).unwrap_err().message
+    }
+
+    #[test]
+    fn bad_bytecode_is_an_error() {
+        assert_eq!(run_code(vec![Instruction::Unused]), "Not enough inputs");
+        assert_eq!(
+            run_code(vec![Instruction::ForNext { var: 0, exit: 1 }]),
+            "No FOR loop is running"
+        );
+    }
+
+    ///
+    /// Runs the program with the interpreter and then the VM under the
+    /// limits, giving the error each of them stops with
+    ///
+    fn limit_errors(source: &str, limits: Limits) -> [RuntimeError; 2] {
+        let ast = parse(source, ScopingModel::Dynamic);
+        let mut output = CapturedOutput::new();
+        let interpreted = Interpreter::new(&mut output)
+            .with_limits(limits)
+            .run(&ast)
+            .unwrap_err();
+        let program = compile(&ast).unwrap();
+        let mut output = CapturedOutput::new();
+        let compiled = Vm::new(&mut output)
+            .with_limits(limits)
+            .run(&program)
+            .unwrap_err();
+        [interpreted, compiled]
+    }
+
+    fn assert_limit(source: &str, limits: Limits, kind: ErrorKind, expected: &str) {
+        for (engine, e) in ["interpreter", "VM"]
+            .iter()
+            .zip(limit_errors(source, limits))
+        {
+            assert_eq!(e.kind, kind, "{engine}");
+            assert_eq!(e.to_string(), expected, "{engine}");
+        }
+    }
+
+    #[test]
+    fn stops_at_step_limit() {
+        let limits = Limits {
+            max_steps: Some(100),
+            ..Limits::default()
+        };
+        assert_limit(
+            "WHILE EQ \"1 \"1 [\n]\n",
+            limits,
+            ErrorKind::StepLimit,
+            "1:1: Step limit reached after running 100 steps",
+        );
+    }
+
+    #[test]
+    fn stops_at_depth_limit() {
+        let limits = Limits {
+            max_depth: Some(10),
+            ..Limits::default()
+        };
+        assert_limit(
+            "TO f\n    f\nEND\nf\n",
+            limits,
+            ErrorKind::DepthLimit,
+            "2:5: Depth limit reached with 10 nested function calls",
+        );
+    }
+
+    #[test]
+    fn deep_calls_do_not_overflow_the_stack() {
+        // far deeper than fits on the stack of a test thread
+        let limits = Limits {
+            max_depth: Some(10_000),
+            ..Limits::default()
+        };
+        assert_limit(
+            "TO f :n\n    f + :n \"1\nEND\nf \"0\n",
+            limits,
+            ErrorKind::DepthLimit,
+            "2:5: Depth limit reached with 10000 nested function calls",
+        );
+    }
+
+    #[test]
+    fn stops_at_segment_limit() {
+        let limits = Limits {
+            max_segments: Some(5),
+            ..Limits::default()
+        };
+        assert_limit(
+            "PENDOWN\nWHILE EQ \"1 \"1 [\n    FORWARD \"1\n]\n",
+            limits,
+            ErrorKind::SegmentLimit,
+            "3:5: Segment limit reached after drawing 5 segments",
+        );
+    }
+
+    #[test]
+    fn stops_at_timeout() {
+        let limits = Limits {
+            timeout: Some(Duration::from_millis(10)),
+            ..Limits::default()
+        };
+        assert_limit(
+            "WHILE EQ \"1 \"1 [\n]\n",
+            limits,
+            ErrorKind::Timeout,
+            "1:1: Timed out after 0.01 seconds",
+        );
+    }
+}